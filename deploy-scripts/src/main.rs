@@ -88,7 +88,13 @@ async fn main() -> Result<(), Error> {
     // // Write your own deployment/initialization script below. An example is given
     // // here.
 
-    // let param: OwnedParameter = OwnedParameter::empty(); // Example
+    // let init_params = InitParams {
+    //     token: ContractAddress::new(7656, 0),
+    //     token_id: TokenIdVec(Vec::new()),
+    //     reward_rate_bps: 1_000,
+    //     min_lock_duration: contracts_common::Duration::from_days(30),
+    // };
+    // let param: OwnedParameter = OwnedParameter::from_serial(&init_params)?; // Example
 
     // let init_method_name: &str = "init_gonana_staking_smart_contract"; // Example
 
@@ -105,14 +111,16 @@ async fn main() -> Result<(), Error> {
     //     .context("Failed to initialize the contract.")?; // Example
 
     // This is how you can use a type from your smart contract.
-    use gonana_staking_smart_contract::{StakeParams, ApproveParam}; // Example
+    use gonana_staking_smart_contract::{StakeParams, ApproveParam, ReleaseFundsParams}; // Example
 
 
     let amount = TokenAmountU64(1000);
 
     let stake_parameter: StakeParams = StakeParams {
-        staker: deployer.key.address,
+        beneficiary: None,
         amount,
+        stake_id: None,
+        tier: None,
     }; // Example
     let token_id = TokenIdUnit();
 
@@ -174,7 +182,7 @@ async fn main() -> Result<(), Error> {
         amount: Amount::from_ccd(0),
         address: ContractAddress::new(7669,0),
         receive_name: OwnedReceiveName::new_unchecked("gonana_staking_smart_contract.release_funds".to_string()),
-        message: OwnedParameter::empty(),
+        message: OwnedParameter::from_serial(&ReleaseFundsParams { stake_id: 1 })?,
     }; // Example
 
 
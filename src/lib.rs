@@ -22,11 +22,16 @@ pub struct  ApproveParam {
 
 
 
-/// smallest possible token ID.
-pub type ContractTokenId = TokenIdUnit;
+/// Token ID of the staked token. Variable length so any CIS-2 token can be
+/// configured; the unit token ID of GONA is the empty ID.
+pub type ContractTokenId = TokenIdVec;
 pub type ContractTokenAmount = TokenAmountU64;
 
-pub const TOKEN_ID_GONA:ContractTokenId = TokenIdUnit();
+/// Identifier of a single stake position.
+pub type StakeId = u64;
+
+/// Identifier of a lock tier.
+pub type TierId = u8;
 
 
 /// Enum representing the possible states of a product
@@ -41,12 +46,44 @@ pub enum StakeEntryState {
 
 
 /// Struct to represent information about a stake.
-#[derive(Serialize, SchemaType, PartialEq, Eq, Clone, Debug)]
+#[derive(Serial, SchemaType, PartialEq, Eq, Clone, Debug)]
 pub struct StakeEntry {
-    pub staker: AccountAddress,
+    /// The account or contract owning the stake.
+    pub staker: Address,
      pub amount: TokenAmountU64,
+    /// The lock tier of the stake, or `None` for the default lock terms.
+    pub tier: Option<TierId>,
+    /// Time from which rewards accrue.
+    pub accrual_start: Timestamp,
+    /// Rewards accrued before `accrual_start` that have not been paid out.
+    pub pending_rewards: TokenAmountU64,
+    /// Time before which the stake cannot be released.
     pub release_time: Timestamp,
-    pub state: StakeEntryState
+    /// Whether accrued rewards are added to the stake whenever it is topped
+    /// up or unstaked from, instead of being paid out.
+    pub auto_compound: bool,
+    pub state: StakeEntryState,
+    /// The reward index of the rate class of the stake at `accrual_start`.
+    pub reward_index: u128,
+}
+
+/// Positions closed before version 3 of the state keep their old layout,
+/// which ends before `reward_index`, as `migrate` only rewrites active
+/// positions. Their index reads as 0; nothing reads it again.
+impl Deserial for StakeEntry {
+    fn deserial<R: Read>(source: &mut R) -> ParseResult<Self> {
+        Ok(StakeEntry {
+            staker: source.get()?,
+            amount: source.get()?,
+            tier: source.get()?,
+            accrual_start: source.get()?,
+            pending_rewards: source.get()?,
+            release_time: source.get()?,
+            auto_compound: source.get()?,
+            state: source.get()?,
+            reward_index: source.get().unwrap_or(0),
+        })
+    }
 }
 
 
@@ -56,14 +93,117 @@ pub struct StakeEntry {
 pub struct SpendParam {
   pub amount: TokenAmountU64,
   pub owner: Address,
-  pub token_id: TokenIdUnit,
+  pub token_id: ContractTokenId,
 } 
 
 
 
+impl StakeEntry {
+
+    /// Moves the rewards accrued until `now` on `reward_index`, the index of
+    /// the rate class of the stake, into `pending_rewards` and restarts
+    /// accrual from `now`. Must be called before `amount` changes so no
+    /// accrued reward is lost.
+    pub fn settle(&mut self, reward_index: &RewardIndex, now: Timestamp) -> Result<(), StakingError> {
+        let index = reward_index.at(now)?;
+        let reward = reward_between(self.amount, self.reward_index, index)?;
+        self.pending_rewards = checked_add(self.pending_rewards, reward)?;
+        self.accrual_start = now;
+        self.reward_index = index;
+        Ok(())
+    }
+
+    /// All rewards owed at `at`, without settling them.
+    pub fn rewards_at(&self, reward_index: &RewardIndex, at: Timestamp) -> Result<ContractTokenAmount, StakingError> {
+        ensure!(at >= self.accrual_start, StakingError::InvalidTimeOrder);
+        let reward = reward_between(self.amount, self.reward_index, reward_index.at(at)?)?;
+        checked_add(self.pending_rewards, reward)
+    }
+
+    /// Settles and removes the rewards owed until `now`, up to `available`,
+    /// returning them. The rest stays in `pending_rewards`.
+    pub fn take_rewards(&mut self, reward_index: &RewardIndex, now: Timestamp, available: ContractTokenAmount) -> Result<ContractTokenAmount, StakingError> {
+        self.settle(reward_index, now)?;
+        let reward = self.pending_rewards.min(available);
+        self.pending_rewards -= reward;
+        Ok(reward)
+    }
+
+    /// Adds the rewards owed until `now`, up to `available`, to `amount`,
+    /// returning the rewards added.
+    pub fn compound(&mut self, reward_index: &RewardIndex, now: Timestamp, available: ContractTokenAmount) -> Result<ContractTokenAmount, StakingError> {
+        let reward = self.take_rewards(reward_index, now, available)?;
+        self.amount = checked_add(self.amount, reward)?;
+        Ok(reward)
+    }
+
+    /// Adds `amount` to the stake, first settling or compounding the rewards
+    /// accrued so far, up to `available`. Returns the rewards compounded.
+    pub fn top_up(&mut self, amount: ContractTokenAmount, reward_index: &RewardIndex, now: Timestamp, available: ContractTokenAmount) -> Result<ContractTokenAmount, StakingError> {
+        let mut compounded = TokenAmountU64(0);
+        if self.auto_compound {
+            compounded = self.compound(reward_index, now, available)?;
+        } else {
+            self.settle(reward_index, now)?;
+        }
+        self.amount = checked_add(self.amount, amount)?;
+        Ok(compounded)
+    }
+}
+
+
+
+
+/// The rewards accrued per token in a rate class, the default lock terms or a
+/// lock tier, since the epoch: the sum of the annual rate in basis points
+/// times the milliseconds it applied. A stake earns the increase of the index
+/// since its accrual started, so a rate change only affects accrual from the
+/// change on.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct RewardIndex {
+    /// Current annual reward rate of the class in basis points.
+    pub reward_rate_bps: u64,
+    /// The index at `updated`.
+    pub accumulated: u128,
+    /// Time of the last rate change.
+    pub updated: Timestamp,
+}
+
+
+
+
+impl RewardIndex {
+
+    /// The index of a class that accrued at `reward_rate_bps` since the epoch.
+    pub fn new(reward_rate_bps: u64, now: Timestamp) -> Self {
+        RewardIndex {
+            reward_rate_bps,
+            accumulated: u128::from(reward_rate_bps) * u128::from(now.timestamp_millis()),
+            updated: now,
+        }
+    }
+
+    /// The index at `at`, which must not be before the last rate change.
+    pub fn at(&self, at: Timestamp) -> Result<u128, StakingError> {
+        let accrued = u128::from(self.reward_rate_bps) * u128::from(elapsed(self.updated, at)?.millis());
+        self.accumulated.checked_add(accrued).ok_or(StakingError::Overflow)
+    }
+
+    /// Changes the rate to `reward_rate_bps` from `now` on.
+    pub fn set_rate(&mut self, reward_rate_bps: u64, now: Timestamp) -> Result<(), StakingError> {
+        self.accumulated = self.at(now)?;
+        self.updated = now;
+        self.reward_rate_bps = reward_rate_bps;
+        Ok(())
+    }
+}
+
+
+
+
 impl SpendParam {
 
-     fn new(amount: TokenAmountU64, owner: Address, token_id: TokenIdUnit) -> Self {
+     fn new(amount: TokenAmountU64, owner: Address, token_id: ContractTokenId) -> Self {
         SpendParam { amount, owner, token_id }
     }
 
@@ -71,19 +211,107 @@ impl SpendParam {
 
 
 
-/// Error types
-#[derive(Debug, PartialEq, Eq, Clone, Reject, Serialize, SchemaType)]
+/// Error types. Each variant has a fixed discriminant, which is also its
+/// serialized tag, and the contract rejects with error code
+/// `-(discriminant + 1)`. Existing discriminants never change; new variants
+/// take the next free one.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, SchemaType)]
+#[repr(u8)]
+#[concordium(repr(u8))]
 pub enum StakingError {
-    StakingNotFound,
-    InsufficientFunds,
-    InvalidPrice,
-    InvalidReleaseTime,
-    InvalidStakingState,
-    #[from(ParseError)]
-    ParseParams,
-    #[from(TransferError)]
-    TransferError,
-    ContractInvokeError,
+    #[concordium(tag = 0)]
+    StakingNotFound = 0,
+    #[concordium(tag = 1)]
+    InsufficientFunds = 1,
+    #[concordium(tag = 2)]
+    InvalidPrice = 2,
+    #[concordium(tag = 3)]
+    InvalidReleaseTime = 3,
+    #[concordium(tag = 4)]
+    InvalidStakingState = 4,
+    #[concordium(tag = 5)]
+    ParseParams = 5,
+    #[concordium(tag = 6)]
+    TransferError = 6,
+    #[concordium(tag = 7)]
+    ContractInvokeError = 7,
+    #[concordium(tag = 8)]
+    Unauthorized = 8,
+    /// An amount or time computation does not fit in its type.
+    #[concordium(tag = 9)]
+    Overflow = 9,
+    /// A timestamp lies before the timestamp it is measured from.
+    #[concordium(tag = 10)]
+    InvalidTimeOrder = 10,
+    #[concordium(tag = 11)]
+    ContractPaused = 11,
+    #[concordium(tag = 12)]
+    InvalidLockTier = 12,
+    /// The reward pool cannot cover a reward without using staked principal.
+    #[concordium(tag = 13)]
+    RewardPoolExhausted = 13,
+    /// Tokens were received from a contract other than the configured token,
+    /// or with a different token ID.
+    #[concordium(tag = 14)]
+    InvalidToken = 14,
+    /// The staker to stake for is missing or inconsistent.
+    #[concordium(tag = 15)]
+    InvalidBeneficiary = 15,
+    /// An event could not be logged.
+    #[concordium(tag = 16)]
+    LogError = 16,
+    /// The contract could not be upgraded to the given module.
+    #[concordium(tag = 17)]
+    UpgradeError = 17,
+    /// A stake of no tokens.
+    #[concordium(tag = 18)]
+    InvalidAmount = 18,
+    /// A page limit of zero or above `MAX_STAKES_PAGE_LIMIT`.
+    #[concordium(tag = 19)]
+    InvalidPageLimit = 19,
+}
+
+
+
+impl StakingError {
+
+    /// The error code the contract rejects with.
+    pub fn error_code(self) -> i32 { -(self as i32) - 1 }
+}
+
+
+
+impl From<StakingError> for Reject {
+    fn from(error: StakingError) -> Self {
+        Reject {
+            error_code: num::NonZeroI32::new(error.error_code()).unwrap_abort(),
+            return_value: Some(to_bytes(&error)),
+        }
+    }
+}
+
+
+
+impl From<ParseError> for StakingError {
+    fn from(_: ParseError) -> Self { Self::ParseParams }
+}
+
+
+
+impl From<TransferError> for StakingError {
+    fn from(_: TransferError) -> Self { Self::TransferError }
+}
+
+
+
+impl From<LogError> for StakingError {
+    fn from(_: LogError) -> Self { Self::LogError }
+}
+
+
+
+impl From<UpgradeError> for StakingError {
+    fn from(_: UpgradeError) -> Self { Self::UpgradeError }
 }
 
 
@@ -96,10 +324,151 @@ impl<A> From<CallContractError<A>> for StakingError {
 
 
 
+/// Events logged by the contract, so indexers and wallets can follow stakes,
+/// releases and reward payments without replaying the state.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Clone, Debug)]
+pub enum StakingEvent {
+    /// A new position was opened for `staker`, paid by `payer`.
+    Staked {
+        stake_id: StakeId,
+        staker: Address,
+        payer: Address,
+        amount: ContractTokenAmount,
+        tier: Option<TierId>,
+        release_time: Timestamp,
+    },
+    /// An active position was topped up by `payer` and locked until
+    /// `release_time`.
+    ToppedUp {
+        stake_id: StakeId,
+        staker: Address,
+        payer: Address,
+        amount: ContractTokenAmount,
+        release_time: Timestamp,
+    },
+    /// A position was released, paying out its principal. It is closed unless
+    /// rewards the reward pool could not cover are still owed on it.
+    Released {
+        stake_id: StakeId,
+        staker: Address,
+        amount: ContractTokenAmount,
+    },
+    /// Part of the principal of a position was withdrawn.
+    Unstaked {
+        stake_id: StakeId,
+        staker: Address,
+        amount: ContractTokenAmount,
+    },
+    /// Rewards of a position were paid out from the reward pool.
+    RewardPaid {
+        stake_id: StakeId,
+        staker: Address,
+        amount: ContractTokenAmount,
+    },
+    /// Rewards of a position were added to its principal.
+    Compounded {
+        stake_id: StakeId,
+        staker: Address,
+        amount: ContractTokenAmount,
+    },
+    /// A position was opted in or out of auto-compounding.
+    AutoCompoundChanged {
+        stake_id: StakeId,
+        auto_compound: bool,
+    },
+    /// A contract staker chose the entrypoint receiving its payouts.
+    PayoutEntrypointSet {
+        contract: ContractAddress,
+        entrypoint: OwnedEntrypointName,
+    },
+    /// Tokens were added to the reward pool.
+    RewardsFunded {
+        from: Address,
+        amount: ContractTokenAmount,
+    },
+    /// The base reward rate was changed.
+    RateChanged {
+        reward_rate_bps: u64,
+    },
+    /// The minimum lock duration was changed.
+    MinLockDurationChanged {
+        min_lock_duration: Duration,
+    },
+    /// A lock tier was added or changed.
+    LockTierSet {
+        tier: TierId,
+        lock_tier: LockTier,
+    },
+    /// The operations halted by the admin were changed.
+    Paused(PauseFlags),
+    /// A new admin was proposed.
+    AdminProposed {
+        pending_admin: AccountAddress,
+    },
+    /// The proposed admin accepted the admin role.
+    AdminChanged {
+        admin: AccountAddress,
+    },
+}
+
+
+
+
+
+
+
+/// Parameter for the `init` function.
+#[derive(Serialize, SchemaType)]
+pub struct InitParams {
+    /// The CIS-2 token contract holding the staked tokens.
+    pub token: ContractAddress,
+    /// The ID of the staked token within `token`.
+    pub token_id: ContractTokenId,
+    /// Annual reward rate in basis points.
+    pub reward_rate_bps: u64,
+    /// How long a stake is locked before it can be released.
+    pub min_lock_duration: Duration,
+}
+
+
+
+
+
+
+
+/// Parameter for `stake_funds`. The tokens are always paid by the sender; the
+/// position belongs to the beneficiary.
 #[derive(Serialize, SchemaType)]
 pub struct StakeParams {
-    pub staker: AccountAddress,
+    /// The account or contract to stake for, or `None` to stake for the
+    /// sender.
+    pub beneficiary: Option<Address>,
     pub amount: ContractTokenAmount,
+    /// The position of the beneficiary to top up, or `None` to open a new
+    /// position.
+    pub stake_id: Option<StakeId>,
+    /// The lock tier of the position, or `None` for the default lock terms.
+    /// Must match the tier of the position when topping up.
+    pub tier: Option<TierId>,
+}
+
+
+
+
+
+
+
+/// Staking instructions in the `AdditionalData` of a CIS-2 transfer to
+/// `onReceivingCIS2`.
+#[derive(Serialize, SchemaType, Default)]
+pub struct StakeTransferData {
+    /// The account or contract to stake for, or `None` for the sender of the
+    /// tokens.
+    pub beneficiary: Option<Address>,
+    /// The position to top up, or `None` to open a new position.
+    pub stake_id: Option<StakeId>,
+    /// The lock tier of the position, or `None` for the default lock terms.
+    pub tier: Option<TierId>,
 }
 
 
@@ -110,7 +479,7 @@ pub struct StakeParams {
 
 #[derive(Serialize, SchemaType)]
 pub struct ReleaseFundsParams {
-    pub token_id: ContractTokenId
+    pub stake_id: StakeId
 }
 
 
@@ -120,11 +489,176 @@ pub struct ReleaseFundsParams {
 
 
 #[derive(Serialize, SchemaType)]
-struct UpgradeParams {
+pub struct SetAutoCompoundParams {
+    pub stake_id: StakeId,
+    pub auto_compound: bool,
+}
+
+
+
+
+
+
+
+#[derive(Serialize, SchemaType)]
+pub struct UnstakeParams {
+    pub stake_id: StakeId,
+    /// The amount of principal to withdraw.
+    pub amount: ContractTokenAmount,
+}
+
+
+
+
+
+
+
+/// Lock term offered to stakers. Longer locks can earn a higher reward.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Clone, Debug)]
+pub struct LockTier {
+    /// How long stakes in this tier are locked.
+    pub lock_duration: Duration,
+    /// Multiplier of the base reward rate in basis points, e.g. 15_000 pays
+    /// 1.5 times the base rate.
+    pub reward_multiplier_bps: u64,
+}
+
+
+
+
+
+
+
+#[derive(Serialize, SchemaType)]
+pub struct SetLockTierParams {
+    pub tier: TierId,
+    pub lock_tier: LockTier,
+}
+
+
+
+
+
+
+
+/// Operations that are halted by the admin. Staking and releases are paused
+/// separately so new stakes can be stopped while users can still withdraw.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct PauseFlags {
+    /// Halts `stake_funds`, `onReceivingCIS2` and `compound`.
+    pub staking: bool,
+    /// Halts `release_funds`, `unstake` and `claim_rewards`.
+    pub releases: bool,
+}
+
+
+
+
+
+
+
+/// Parameter for the `view_pending_rewards` function.
+#[derive(Serialize, SchemaType)]
+pub struct ViewPendingRewardsParams {
+    pub staker: Address,
+    /// The position to preview, or `None` for all active positions of the
+    /// staker.
+    pub stake_id: Option<StakeId>,
+    /// The time to compute the accrued rewards at, or `None` for now. Must
+    /// not be before now.
+    pub at: Option<Timestamp>,
+}
+
+
+
+
+
+
+
+/// Preview of the rewards of a position.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Clone, Debug)]
+pub struct PendingRewards {
+    pub stake_id: StakeId,
+    /// The staked amount.
+    pub principal: ContractTokenAmount,
+    /// The rewards owed at the requested time.
+    pub accrued_reward: ContractTokenAmount,
+    /// Time before which the stake cannot be released.
+    pub release_time: Timestamp,
+    /// What `release_funds` would pay out now: the principal and the rewards
+    /// owed now that the reward pool covers, or nothing while the stake is
+    /// locked.
+    pub release_payout: ContractTokenAmount,
+}
+
+
+
+
+
+
+
+/// Parameter for the `view_stakes` function.
+#[derive(Serialize, SchemaType)]
+pub struct ViewStakesParams {
+    /// The ID to start the page at, or `None` for the first position. Use the
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<StakeId>,
+    /// How many consecutive IDs the page covers, from 1 up to
+    /// `MAX_STAKES_PAGE_LIMIT`.
+    pub limit: u32,
+    /// Only list positions in this state, or `None` for all positions.
+    pub state: Option<StakeEntryState>,
+}
+
+
+
+
+
+
+
+/// A page of positions returned by `view_stakes`.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Clone, Debug)]
+pub struct StakesPage {
+    /// The positions of the page with their IDs. Each entry holds its staker.
+    pub stakes: Vec<(StakeId, StakeEntry)>,
+    /// The cursor of the next page, or `None` if this is the last page.
+    pub next_cursor: Option<StakeId>,
+}
+
+
+
+
+
+
+
+/// Aggregate figures of the staking pool.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Clone, Debug)]
+pub struct PoolStats {
+    /// Total principal of all active positions.
+    pub total_staked: ContractTokenAmount,
+    /// Number of stakers with at least one active position.
+    pub active_stakers: u64,
+    /// Rewards taken from the reward pool so far, paid out or compounded.
+    pub total_rewards_paid: ContractTokenAmount,
+    /// Annual base reward rate in basis points.
+    pub reward_rate_bps: u64,
+    /// Tokens funded for paying rewards that have not been paid out yet.
+    pub reward_pool_balance: ContractTokenAmount,
+}
+
+
+
+
+
+
+
+/// Parameter for the `upgrade` function.
+#[derive(Serialize, SchemaType)]
+pub struct UpgradeParams {
     /// The new module reference.
-    module:  ModuleReference,
+    pub module:  ModuleReference,
     /// Optional entrypoint to call in the new module after upgrade.
-    migrate: Option<(OwnedEntrypointName, OwnedParameter)>,
+    pub migrate: Option<(OwnedEntrypointName, OwnedParameter)>,
 }
 
 
@@ -137,128 +671,692 @@ struct UpgradeParams {
 #[derive(Serial, DeserialWithState)]
 #[concordium(state_parameter = "S")]
 pub struct State<S = StateApi> {
-    pub stake_entries: StateMap<AccountAddress, StakeEntry, S>,
-    pub next_stake_id: u64,
+    pub stake_entries: StateMap<StakeId, StakeEntry, S>,
+    /// The active positions of each staker.
+    pub account_stakes: StateMap<Address, StateSet<StakeId, S>, S>,
+    /// The closed positions of each staker, kept as history.
+    pub closed_stakes: StateMap<Address, StateSet<StakeId, S>, S>,
+    pub next_stake_id: StakeId,
+    /// The CIS-2 token contract holding the staked tokens.
+    pub token: ContractAddress,
+    /// The ID of the staked token within `token`.
+    pub token_id: ContractTokenId,
+    /// Annual reward rate in basis points, e.g. 1_000 is 10% APY.
+    pub reward_rate_bps: u64,
+    /// The account allowed to call privileged entrypoints.
+    pub admin: AccountAddress,
+    /// The account proposed as the next admin, until it accepts.
+    pub pending_admin: Option<AccountAddress>,
+    /// Operations currently halted by the admin.
+    pub paused: PauseFlags,
+    /// How long a stake is locked before it can be released.
+    pub min_lock_duration: Duration,
+    /// Lock terms stakers can choose from instead of the default ones.
+    pub lock_tiers: StateMap<TierId, LockTier, S>,
+    /// Total principal of all active positions.
+    pub total_staked: ContractTokenAmount,
+    /// Tokens funded for paying rewards that have not been paid out yet.
+    pub reward_pool_balance: ContractTokenAmount,
+    /// The entrypoint receiving the payouts of each contract staker that
+    /// chose one other than `onReceivingCIS2`.
+    pub payout_entrypoints: StateMap<ContractAddress, OwnedEntrypointName, S>,
+    /// The version of this layout of the state, `STATE_VERSION`. Fields added
+    /// in later versions follow it.
+    pub state_version: u32,
+    /// Number of stakers with at least one active position.
+    pub active_stakers: u64,
+    /// Rewards taken from the reward pool so far, paid out or compounded.
+    pub total_rewards_paid: ContractTokenAmount,
+    /// The reward index of the default lock terms, under `None`, and of each
+    /// lock tier.
+    pub reward_indexes: StateMap<Option<TierId>, RewardIndex, S>,
+}
+
+
+
+
+
+/// The layout of `State` before it was versioned, migrated by `migrate`. Later
+/// layouts start with the same fields, followed by `state_version`.
+#[derive(DeserialWithState)]
+#[concordium(state_parameter = "S")]
+struct StateV0<S = StateApi> {
+    stake_entries: StateMap<StakeId, StakeEntry, S>,
+    account_stakes: StateMap<Address, StateSet<StakeId, S>, S>,
+    closed_stakes: StateMap<Address, StateSet<StakeId, S>, S>,
+    next_stake_id: StakeId,
+    token: ContractAddress,
+    token_id: ContractTokenId,
+    reward_rate_bps: u64,
+    admin: AccountAddress,
+    pending_admin: Option<AccountAddress>,
+    paused: PauseFlags,
+    min_lock_duration: Duration,
+    lock_tiers: StateMap<TierId, LockTier, S>,
+    total_staked: ContractTokenAmount,
+    reward_pool_balance: ContractTokenAmount,
+    payout_entrypoints: StateMap<ContractAddress, OwnedEntrypointName, S>,
 }
 
 
 
 
 
-// Constants for the reward rate and seconds in a year
-const REWARD_RATE: u64 = 1; // 100% APY
+/// The version of the current layout of `State`. Bump it, and migrate from the
+/// previous layout in `migrate`, whenever the layout changes.
+pub const STATE_VERSION: u32 = 3;
+
+/// The most IDs a page of `view_stakes` can cover.
+pub const MAX_STAKES_PAGE_LIMIT: u32 = 100;
+
+// Constants for the reward rate and milliseconds in a (365 day) year
+pub const BASIS_POINTS: u64 = 10_000;
+pub const MILLIS_PER_YEAR: u64 = 365 * 24 * 60 * 60 * 1000;
+
+
+
+
+/// Computes the reward accrued by staking `amount` for `duration` at an annual
+/// rate of `reward_rate_bps` basis points:
+///
+/// `reward = amount * reward_rate_bps * duration_ms / (BASIS_POINTS * MILLIS_PER_YEAR)`
+///
+/// The product is computed exactly in 128-bit fixed point and divided once at
+/// the end, so the only rounding is the final one, which is always down to
+/// the nearest token unit. The contract therefore never pays out more than
+/// has accrued. Fails with `StakingError::Overflow` if the product or the
+/// reward does not fit.
+pub fn calculate_reward(amount: ContractTokenAmount, reward_rate_bps: u64, duration: Duration) -> Result<ContractTokenAmount, StakingError> {
+    reward_for(amount, u128::from(reward_rate_bps) * u128::from(duration.millis()))
+}
+
+
+
+/// Computes the reward accrued by staking `amount` while the reward index
+/// grew from `from` to `to`. Equal to `calculate_reward` while the rate does
+/// not change, with the same rounding.
+pub fn reward_between(amount: ContractTokenAmount, from: u128, to: u128) -> Result<ContractTokenAmount, StakingError> {
+    reward_for(amount, to.checked_sub(from).ok_or(StakingError::InvalidTimeOrder)?)
+}
+
+
+
+/// The reward on `amount` for `rate_millis`, an annual rate in basis points
+/// times the milliseconds it applied.
+fn reward_for(amount: ContractTokenAmount, rate_millis: u128) -> Result<ContractTokenAmount, StakingError> {
+    let accrued = u128::from(amount.0).checked_mul(rate_millis).ok_or(StakingError::Overflow)?;
+    let reward = accrued / (u128::from(BASIS_POINTS) * u128::from(MILLIS_PER_YEAR));
+    u64::try_from(reward).map(TokenAmountU64).map_err(|_| StakingError::Overflow)
+}
+
+
+
+/// Adds two token amounts, failing with `StakingError::Overflow` instead of
+/// wrapping.
+pub fn checked_add(a: ContractTokenAmount, b: ContractTokenAmount) -> Result<ContractTokenAmount, StakingError> {
+    a.0.checked_add(b.0).map(TokenAmountU64).ok_or(StakingError::Overflow)
+}
+
+
+
+/// Subtracts `b` from `a`, failing with `StakingError::Overflow` instead of
+/// wrapping.
+pub fn checked_sub(a: ContractTokenAmount, b: ContractTokenAmount) -> Result<ContractTokenAmount, StakingError> {
+    a.0.checked_sub(b.0).map(TokenAmountU64).ok_or(StakingError::Overflow)
+}
+
+
+
+/// Whether a set of positions holds any position. `StateSet::is_empty` only
+/// checks for an entry at the prefix of the set itself, which never exists.
+fn has_stakes(stakes: &StateSet<StakeId, StateApi>) -> bool {
+    stakes.iter().next().is_some()
+}
+
+
+
+/// Time elapsed from `from` until `to`, failing with
+/// `StakingError::InvalidTimeOrder` if `to` is before `from`.
+pub fn elapsed(from: Timestamp, to: Timestamp) -> Result<Duration, StakingError> {
+    to.duration_since(from).ok_or(StakingError::InvalidTimeOrder)
+}
 
 
 
 
 impl State {
 
-     fn empty(state_builder: &mut StateBuilder) -> Self {
+     fn new(state_builder: &mut StateBuilder, params: InitParams, admin: AccountAddress, now: Timestamp) -> Self {
+        let mut reward_indexes = state_builder.new_map();
+        reward_indexes.insert(None, RewardIndex::new(params.reward_rate_bps, now));
         State {
             stake_entries: state_builder.new_map(),
+            account_stakes: state_builder.new_map(),
+            closed_stakes: state_builder.new_map(),
             next_stake_id: 1,
+            token: params.token,
+            token_id: params.token_id,
+            reward_rate_bps: params.reward_rate_bps,
+            admin,
+            pending_admin: None,
+            paused: PauseFlags::default(),
+            min_lock_duration: params.min_lock_duration,
+            lock_tiers: state_builder.new_map(),
+            total_staked: TokenAmountU64(0),
+            reward_pool_balance: TokenAmountU64(0),
+            payout_entrypoints: state_builder.new_map(),
+            state_version: STATE_VERSION,
+            active_stakers: 0,
+            total_rewards_paid: TokenAmountU64(0),
+            reward_indexes,
         }
     }
-}
 
+    /// Ensures that `sender` is the admin.
+    fn ensure_admin(&self, sender: &Address) -> Result<(), StakingError> {
+        ensure!(sender.matches_account(&self.admin), StakingError::Unauthorized);
+        Ok(())
+    }
 
+    /// How long stakes in `tier` are locked.
+    fn lock_duration(&self, tier: Option<TierId>) -> Result<Duration, StakingError> {
+        match tier {
+            Some(tier) => self.lock_tiers.get(&tier).map(|lock_tier| lock_tier.lock_duration).ok_or(StakingError::InvalidLockTier),
+            None => Ok(self.min_lock_duration),
+        }
+    }
+
+    /// The active stake `stake_id`, provided it belongs to `staker`.
+    fn active_stake(&self, stake_id: StakeId, staker: Address) -> Result<StakeEntry, StakingError> {
+        let stake_entry = self.stake_entries.get(&stake_id).ok_or(StakingError::StakingNotFound)?;
+        ensure!(stake_entry.staker == staker, StakingError::Unauthorized);
+        // Ensure that the stake-entry is in an Active state
+        ensure!(stake_entry.state == StakeEntryState::Active, StakingError::InvalidStakingState);
+        Ok(stake_entry.clone())
+    }
+
+    /// Takes `reward` out of the reward pool. Fails rather than paying rewards
+    /// out of staked principal.
+    fn spend_rewards(&mut self, reward: ContractTokenAmount) -> Result<(), StakingError> {
+        self.reward_pool_balance = TokenAmountU64(self.reward_pool_balance.0.checked_sub(reward.0).ok_or(StakingError::RewardPoolExhausted)?);
+        self.total_rewards_paid = checked_add(self.total_rewards_paid, reward)?;
+        Ok(())
+    }
+
+    /// Closes the position `stake_id` and moves it to its staker's history. The
+    /// staker can keep opening new positions.
+    fn close_stake(&mut self, state_builder: &mut StateBuilder, stake_id: StakeId, mut stake_entry: StakeEntry) {
+        stake_entry.state = StakeEntryState::Inactive;
+        if let Some(mut stakes) = self.account_stakes.get_mut(&stake_entry.staker) {
+            stakes.remove(&stake_id);
+            if !has_stakes(&stakes) {
+                self.active_stakers = self.active_stakers.saturating_sub(1);
+            }
+        }
+        self.closed_stakes.entry(stake_entry.staker).or_insert_with(|| state_builder.new_set()).insert(stake_id);
+        self.stake_entries.insert(stake_id, stake_entry);
+    }
 
+    /// Stores the position `stake_id`, closing it once its principal and all
+    /// rewards owed on it are paid out.
+    fn close_stake_if_paid(&mut self, state_builder: &mut StateBuilder, stake_id: StakeId, stake_entry: StakeEntry) {
+        if stake_entry.amount.0 == 0 && stake_entry.pending_rewards.0 == 0 {
+            self.close_stake(state_builder, stake_id, stake_entry);
+        } else {
+            self.stake_entries.insert(stake_id, stake_entry);
+        }
+    }
 
+    /// Where payouts to `staker` are sent. Contracts receive them on their
+    /// chosen payout entrypoint, `onReceivingCIS2` by default.
+    fn payout_receiver(&self, staker: Address) -> Receiver {
+        match staker {
+            Address::Account(account) => Receiver::Account(account),
+            Address::Contract(contract) => {
+                let entrypoint = self.payout_entrypoints.get(&contract)
+                    .map(|entrypoint| entrypoint.clone())
+                    .unwrap_or_else(|| OwnedEntrypointName::new_unchecked("onReceivingCIS2".into()));
+                Receiver::Contract(contract, entrypoint)
+            }
+        }
+    }
 
+    /// Annual reward rate in basis points of stakes in `tier`.
+    fn reward_rate_bps(&self, tier: Option<TierId>) -> Result<u64, StakingError> {
+        let Some(tier) = tier else {
+            return Ok(self.reward_rate_bps);
+        };
+        let multiplier = self.lock_tiers.get(&tier).ok_or(StakingError::InvalidLockTier)?.reward_multiplier_bps;
+        let rate = u128::from(self.reward_rate_bps) * u128::from(multiplier) / u128::from(BASIS_POINTS);
+        u64::try_from(rate).map_err(|_| StakingError::Overflow)
+    }
 
+    /// The reward index of stakes in `tier`.
+    fn reward_index(&self, tier: Option<TierId>) -> Result<RewardIndex, StakingError> {
+        self.reward_indexes.get(&tier).map(|reward_index| *reward_index).ok_or(StakingError::InvalidLockTier)
+    }
 
-    /// Init function to initialize the staking state
-#[init(contract = "gonana_staking_smart_contract")]
-fn init(_ctx: &InitContext, state_builder: &mut StateBuilder) -> InitResult<State> {
-    Ok(State::empty(state_builder))
+    /// Applies the current rate of stakes in `tier` to their reward index from
+    /// `now` on. Call it after every change of the base rate or of the
+    /// multiplier of `tier`.
+    fn update_reward_index(&mut self, tier: Option<TierId>, now: Timestamp) -> Result<(), StakingError> {
+        let reward_rate_bps = self.reward_rate_bps(tier)?;
+        if let Some(mut reward_index) = self.reward_indexes.get_mut(&tier) {
+            return reward_index.set_rate(reward_rate_bps, now);
+        }
+        self.reward_indexes.insert(tier, RewardIndex::new(reward_rate_bps, now));
+        Ok(())
+    }
 }
 
 
 
-/// Function to handle staking funds
-#[receive(contract = "gonana_staking_smart_contract", name = "stake_funds", parameter = "StakeParams", mutable)]
-fn stake_funds(ctx: &ReceiveContext, host: &mut Host<State>) -> Result<(), StakingError> {
-    let parameter: StakeParams = ctx.parameter_cursor().get()?;
 
-    let amount = parameter.amount;
-    let owner = ctx.sender();
-    let token_id = TokenIdUnit();
-    let gona_token = ContractAddress::new(7656,0);
-    let entry_point= EntrypointName::new_unchecked("transfer_from".into());
-    let spend_param = SpendParam::new(amount, owner, token_id);
 
-    
 
-    // Check if an AccountAddress has staked before 
-    if let Some(mut stake_entry) = host.state_mut().stake_entries.remove_and_get(&ctx.invoker()){
-             // Ensure that the stake-entry is in an Active state 
-             ensure!(stake_entry.state == StakeEntryState::Active, StakingError::InvalidStakingState);  
-             host.invoke_contract(&gona_token, &spend_param, entry_point, Amount::zero())?; 
-             stake_entry.amount += amount; 
-             stake_entry.release_time = ctx.metadata().block_time();
-             host.state_mut().stake_entries.insert(stake_entry.staker, stake_entry.clone());
-             stake_entry.delete();
+
+    /// Init function to initialize the staking state
+#[init(contract = "gonana_staking_smart_contract", parameter = "InitParams", event = "StakingEvent", error = "StakingError")]
+fn init(ctx: &InitContext, state_builder: &mut StateBuilder) -> Result<State, StakingError> {
+    let params: InitParams = ctx.parameter_cursor().get()?;
+    Ok(State::new(state_builder, params, ctx.init_origin(), ctx.metadata().block_time()))
+}
+
+
+
+/// Records a stake paid by `payer` that this contract has already received.
+/// Opens a new position for the beneficiary, or tops up one of theirs, and
+/// returns its ID. Without a beneficiary the payer stakes for itself. Only the
+/// staker's own top-ups restart the lock of the position, so nobody else can
+/// keep it locked.
+fn record_stake(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger, payer: Address, parameter: StakeParams) -> Result<StakeId, StakingError> {
+    let staker = parameter.beneficiary.unwrap_or(payer);
+    // Tokens staked for this contract could never be released
+    ensure!(!staker.matches_contract(&ctx.self_address()), StakingError::InvalidBeneficiary);
+    let amount = parameter.amount;
+    ensure!(amount.0 > 0, StakingError::InvalidAmount);
+    let now = ctx.metadata().block_time();
+    let lock_duration = host.state().lock_duration(parameter.tier)?;
+    let release_time = now.checked_add(lock_duration).ok_or(StakingError::Overflow)?;
+
+    let (state, state_builder) = host.state_and_builder();
+    let stake_id = if let Some(stake_id) = parameter.stake_id {
+        // Top up an existing position of the beneficiary
+        let mut stake_entry = state.active_stake(stake_id, staker)?;
+        ensure!(stake_entry.tier == parameter.tier, StakingError::InvalidLockTier);
+        let reward_index = state.reward_index(stake_entry.tier)?;
+        let compounded = stake_entry.top_up(amount, &reward_index, now, state.reward_pool_balance)?;
+        if payer == staker {
+            stake_entry.release_time = release_time;
+        }
+        let release_time = stake_entry.release_time;
+        if compounded.0 > 0 {
+            logger.log(&StakingEvent::Compounded { stake_id, staker, amount: compounded })?;
+        }
+        state.stake_entries.insert(stake_id, stake_entry);
+        state.spend_rewards(compounded)?;
+        state.total_staked = checked_add(state.total_staked, compounded)?;
+        logger.log(&StakingEvent::ToppedUp { stake_id, staker, payer, amount, release_time })?;
+        stake_id
     } else {
-        // If an AccountAddress has not staked before go ahead to stake_funds
-            host.invoke_contract(&gona_token, &spend_param, entry_point, Amount::zero())?;   
-    
+        // Open a new position
+        let reward_index = state.reward_index(parameter.tier)?.at(now)?;
+        let stake_id = state.next_stake_id;
+        state.next_stake_id = stake_id.checked_add(1).ok_or(StakingError::Overflow)?;
+
         // Store information about the stake in the state
         let stake_info = StakeEntry {
-            staker: parameter.staker,
-            amount: parameter.amount,
-            release_time: ctx.metadata().block_time(),
+            staker,
+            amount,
+            tier: parameter.tier,
+            accrual_start: now,
+            reward_index,
+            pending_rewards: TokenAmountU64(0),
+            release_time,
+            auto_compound: false,
             state: StakeEntryState::Active
         };
+        state.stake_entries.insert(stake_id, stake_info);
+        let mut stakes = state.account_stakes.entry(staker).or_insert_with(|| state_builder.new_set());
+        if !has_stakes(&stakes) {
+            state.active_stakers = state.active_stakers.checked_add(1).ok_or(StakingError::Overflow)?;
+        }
+        stakes.insert(stake_id);
+        logger.log(&StakingEvent::Staked { stake_id, staker, payer, amount, tier: parameter.tier, release_time })?;
+        stake_id
+    };
+    state.total_staked = checked_add(state.total_staked, amount)?;
 
-        host.state_mut().stake_entries.insert(parameter.staker, stake_info);
-    
-        // Update next_stake_id for the next stake
-        host.state_mut().next_stake_id += 1;
-    }
+    Ok(stake_id)
+}
 
-    Ok(())
+
+
+
+/// Function to handle staking funds. Pulls the tokens from the sender with
+/// `transfer_from`, then opens a new position for the beneficiary, or tops up
+/// one of theirs, and returns its ID.
+#[receive(contract = "gonana_staking_smart_contract", name = "stake_funds", parameter = "StakeParams", return_value = "StakeId", error = "StakingError", mutable, enable_logger)]
+fn stake_funds(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<StakeId, StakingError> {
+    ensure!(!host.state().paused.staking, StakingError::ContractPaused);
+    let parameter: StakeParams = ctx.parameter_cursor().get()?;
+
+    let owner = ctx.sender();
+    let token_id = host.state().token_id.clone();
+    let gona_token = host.state().token;
+    let entry_point= EntrypointName::new_unchecked("transfer_from");
+    let spend_param = SpendParam::new(parameter.amount, owner, token_id);
+
+    host.invoke_contract(&gona_token, &spend_param, entry_point, Amount::zero())?;
+
+    record_stake(ctx, host, logger, owner, parameter)
 }
 
 
 
 
-//Function to release the staked funds
-#[receive(contract = "gonana_staking_smart_contract", name = "release_funds", mutable)]
-fn release_funds(ctx: &ReceiveContext, host: &mut Host<State>) -> Result<(), StakingError> {
+/// CIS-2 receive hook to stake in a single `transfer` on the token contract.
+/// The `AdditionalData` of the transfer holds a `StakeTransferData`; empty
+/// data opens a new position with the default lock terms for the sender.
+#[receive(
+    contract = "gonana_staking_smart_contract",
+    name = "onReceivingCIS2",
+    parameter = "OnReceivingCis2Params<ContractTokenId, ContractTokenAmount>",
+    error = "StakingError",
+    mutable,
+    enable_logger
+)]
+fn on_receiving_cis2(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    ensure!(!host.state().paused.staking, StakingError::ContractPaused);
+    let params: OnReceivingCis2Params<ContractTokenId, ContractTokenAmount> = ctx.parameter_cursor().get()?;
 
-    let mut stake_entry = host.state_mut().stake_entries.get_mut(&ctx.invoker()).ok_or(StakingError::StakingNotFound)?;
-    
-    // Ensure that the stake-entry is in a valid state for releasing the funds
-    ensure!(stake_entry.state == StakeEntryState::Active, StakingError::InvalidStakingState);
-    let time = ctx.metadata().block_time().duration_since(stake_entry.release_time).unwrap();
+    // Only accept the configured token
+    let state = host.state();
+    ensure!(ctx.sender().matches_contract(&state.token), StakingError::InvalidToken);
+    ensure!(params.token_id == state.token_id, StakingError::InvalidToken);
 
-    let seconds = time.seconds();
-    let token_id = TokenIdUnit();
-    let gona_token = ContractAddress::new(7656,0);
+    let data: StakeTransferData = if params.data.as_ref().is_empty() {
+        StakeTransferData::default()
+    } else {
+        from_bytes(params.data.as_ref())?
+    };
+    // Stake for the sender of the tokens unless a beneficiary is given
+    record_stake(ctx, host, logger, params.from, StakeParams {
+        beneficiary: data.beneficiary,
+        amount: params.amount,
+        stake_id: data.stake_id,
+        tier: data.tier,
+    })?;
+    Ok(())
+}
+
+
+
+
+/// Transfers `amount` of the staked token from this contract to the staker
+/// `to`.
+fn transfer_tokens(ctx: &ReceiveContext, host: &mut Host<State>, to: Address, amount: ContractTokenAmount) -> Result<(), StakingError> {
+    let token_id = host.state().token_id.clone();
+    let gona_token = host.state().token;
+    let to = host.state().payout_receiver(to);
 
-  
-    let reward_amount =  REWARD_RATE * seconds + stake_entry.amount.0 ;
-    let reward = TokenAmountU64(reward_amount);
-    
     // Create a Transfer instance
     let transfer_payload = Transfer{
         token_id,
-        amount: reward,
-        to: Receiver::Account(stake_entry.staker),
+        amount,
+        to,
         from: Address::Contract(ctx.self_address()),
         data: AdditionalData::empty()
     };
-    let entry_point= EntrypointName::new_unchecked("transfer".into());
+    let entry_point= EntrypointName::new_unchecked("transfer");
+
+    let payload = TransferParams::from(vec![transfer_payload]);
+    host.invoke_contract(&gona_token, &payload, entry_point, Amount::zero())?;
+    Ok(())
+}
+
+
+
+
+//Function to release the staked funds. Pays out the principal with the
+//rewards the reward pool covers; any rest stays owed on the position, to be
+//collected with `claim_rewards`, and the position closes once fully paid
+#[receive(contract = "gonana_staking_smart_contract", name = "release_funds", parameter = "ReleaseFundsParams", error = "StakingError", mutable, enable_logger)]
+fn release_funds(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    ensure!(!host.state().paused.releases, StakingError::ContractPaused);
+    let parameter: ReleaseFundsParams = ctx.parameter_cursor().get()?;
+    let now = ctx.metadata().block_time();
 
-    let mut transfers = Vec::new();
-    transfers.push(transfer_payload);
-    let payload = TransferParams::from(transfers);
-    stake_entry.state = StakeEntryState::Inactive;
-    drop(stake_entry);
+    let state = host.state();
+    let mut stake_entry = state.active_stake(parameter.stake_id, ctx.sender())?;
     //Check if the release time has passed
-   
-        host.invoke_contract(&gona_token, &payload, entry_point, Amount::zero())?;   
-    
+    ensure!(now >= stake_entry.release_time, StakingError::InvalidReleaseTime);
+
+    let reward_index = state.reward_index(stake_entry.tier)?;
+    let reward = stake_entry.take_rewards(&reward_index, now, state.reward_pool_balance)?;
+    let principal = core::mem::take(&mut stake_entry.amount);
+    let payout = checked_add(reward, principal)?;
+
+    let staker = stake_entry.staker;
+    let (state, state_builder) = host.state_and_builder();
+    state.spend_rewards(reward)?;
+    state.total_staked = checked_sub(state.total_staked, principal)?;
+    state.close_stake_if_paid(state_builder, parameter.stake_id, stake_entry);
+
+    if reward.0 > 0 {
+        logger.log(&StakingEvent::RewardPaid { stake_id: parameter.stake_id, staker, amount: reward })?;
+    }
+    logger.log(&StakingEvent::Released { stake_id: parameter.stake_id, staker, amount: principal })?;
+    transfer_tokens(ctx, host, staker, payout)
+}
+
+
+
+
+/// Function to withdraw part of a stake. Pays out the rewards accrued so far
+/// along with the requested amount, and leaves the rest staked. Withdrawing
+/// the whole stake closes the position.
+#[receive(contract = "gonana_staking_smart_contract", name = "unstake", parameter = "UnstakeParams", error = "StakingError", mutable, enable_logger)]
+fn unstake(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    ensure!(!host.state().paused.releases, StakingError::ContractPaused);
+    let parameter: UnstakeParams = ctx.parameter_cursor().get()?;
+    let now = ctx.metadata().block_time();
+
+    let state = host.state();
+    let mut stake_entry = state.active_stake(parameter.stake_id, ctx.sender())?;
+    ensure!(now >= stake_entry.release_time, StakingError::InvalidReleaseTime);
+
+    // Settle the rewards accrued so far, into the stake or paid out, and
+    // restart accrual on the remaining stake
+    let reward_index = state.reward_index(stake_entry.tier)?;
+    let mut compounded = TokenAmountU64(0);
+    let mut reward = TokenAmountU64(0);
+    if stake_entry.auto_compound {
+        compounded = stake_entry.compound(&reward_index, now, state.reward_pool_balance)?;
+    } else {
+        reward = stake_entry.take_rewards(&reward_index, now, state.reward_pool_balance)?;
+    }
+    let payout = checked_add(reward, parameter.amount)?;
+    stake_entry.amount = TokenAmountU64(stake_entry.amount.0.checked_sub(parameter.amount.0).ok_or(StakingError::InsufficientFunds)?);
+
+    let staker = stake_entry.staker;
+    let (state, state_builder) = host.state_and_builder();
+    state.spend_rewards(checked_add(reward, compounded)?)?;
+    state.total_staked = checked_sub(checked_add(state.total_staked, compounded)?, parameter.amount)?;
+    state.close_stake_if_paid(state_builder, parameter.stake_id, stake_entry);
+
+    let stake_id = parameter.stake_id;
+    if compounded.0 > 0 {
+        logger.log(&StakingEvent::Compounded { stake_id, staker, amount: compounded })?;
+    }
+    if reward.0 > 0 {
+        logger.log(&StakingEvent::RewardPaid { stake_id, staker, amount: reward })?;
+    }
+    logger.log(&StakingEvent::Unstaked { stake_id, staker, amount: parameter.amount })?;
+    transfer_tokens(ctx, host, staker, payout)
+}
+
+
+
+
+
+
+
+/// Function to collect the rewards accrued by a stake without unstaking. The
+/// principal stays staked in its tier and keeps its release time.
+#[receive(contract = "gonana_staking_smart_contract", name = "claim_rewards", parameter = "StakeId", error = "StakingError", mutable, enable_logger)]
+fn claim_rewards(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    ensure!(!host.state().paused.releases, StakingError::ContractPaused);
+    let stake_id: StakeId = ctx.parameter_cursor().get()?;
+    let now = ctx.metadata().block_time();
+
+    let state = host.state();
+    let mut stake_entry = state.active_stake(stake_id, ctx.sender())?;
+
+    // Pay out the rewards accrued so far and restart accrual from now
+    let reward_index = state.reward_index(stake_entry.tier)?;
+    let reward = stake_entry.take_rewards(&reward_index, now, state.reward_pool_balance)?;
+    // Rewards are owed but the reward pool covers none of them
+    ensure!(reward.0 > 0 || stake_entry.pending_rewards.0 == 0, StakingError::RewardPoolExhausted);
+
+    let staker = stake_entry.staker;
+    let (state, state_builder) = host.state_and_builder();
+    state.spend_rewards(reward)?;
+    state.close_stake_if_paid(state_builder, stake_id, stake_entry);
+
+    // Nothing to pay out yet
+    if reward.0 == 0 {
+        return Ok(());
+    }
+    logger.log(&StakingEvent::RewardPaid { stake_id, staker, amount: reward })?;
+    transfer_tokens(ctx, host, staker, reward)
+}
+
+
+
+
+
+
+
+/// Function to add the rewards accrued by a stake to its amount, so they
+/// earn rewards themselves. The stake keeps its release time.
+#[receive(contract = "gonana_staking_smart_contract", name = "compound", parameter = "StakeId", error = "StakingError", mutable, enable_logger)]
+fn compound(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    ensure!(!host.state().paused.staking, StakingError::ContractPaused);
+    let stake_id: StakeId = ctx.parameter_cursor().get()?;
+    let now = ctx.metadata().block_time();
+
+    let state = host.state_mut();
+    let mut stake_entry = state.active_stake(stake_id, ctx.sender())?;
+    let reward_index = state.reward_index(stake_entry.tier)?;
+    let compounded = stake_entry.compound(&reward_index, now, state.reward_pool_balance)?;
+    ensure!(compounded.0 > 0 || stake_entry.pending_rewards.0 == 0, StakingError::RewardPoolExhausted);
+    // Nothing accrued yet
+    if compounded.0 == 0 {
+        return Ok(());
+    }
+    let staker = stake_entry.staker;
+    state.stake_entries.insert(stake_id, stake_entry);
+    state.spend_rewards(compounded)?;
+    state.total_staked = checked_add(state.total_staked, compounded)?;
+    logger.log(&StakingEvent::Compounded { stake_id, staker, amount: compounded })?;
+    Ok(())
+}
+
+
+
+
+
+
+
+/// Function to opt a stake in or out of compounding its rewards whenever it
+/// is topped up or unstaked from
+#[receive(contract = "gonana_staking_smart_contract", name = "set_auto_compound", parameter = "SetAutoCompoundParams", error = "StakingError", mutable, enable_logger)]
+fn set_auto_compound(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    let parameter: SetAutoCompoundParams = ctx.parameter_cursor().get()?;
+
+    let state = host.state_mut();
+    let mut stake_entry = state.active_stake(parameter.stake_id, ctx.sender())?;
+    stake_entry.auto_compound = parameter.auto_compound;
+    state.stake_entries.insert(parameter.stake_id, stake_entry);
+    logger.log(&StakingEvent::AutoCompoundChanged { stake_id: parameter.stake_id, auto_compound: parameter.auto_compound })?;
+    Ok(())
+}
+
+
+
+
+
+
+
+/// Function for a contract staker to choose the entrypoint its payouts are
+/// sent to. Without one, payouts go to `onReceivingCIS2`.
+#[receive(contract = "gonana_staking_smart_contract", name = "set_payout_entrypoint", parameter = "OwnedEntrypointName", error = "StakingError", mutable, enable_logger)]
+fn set_payout_entrypoint(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    // Accounts always receive payouts directly
+    let Address::Contract(contract) = ctx.sender() else {
+        bail!(StakingError::Unauthorized);
+    };
+    let entrypoint: OwnedEntrypointName = ctx.parameter_cursor().get()?;
+
+    host.state_mut().payout_entrypoints.insert(contract, entrypoint.clone());
+    logger.log(&StakingEvent::PayoutEntrypointSet { contract, entrypoint })?;
+    Ok(())
+}
+
+
+
+
+
+
+
+/// Function to fund the reward pool. Called by the token contract when GONA
+/// is transferred to this entrypoint; anyone can fund the pool.
+#[receive(
+    contract = "gonana_staking_smart_contract",
+    name = "fund_rewards",
+    parameter = "OnReceivingCis2Params<ContractTokenId, ContractTokenAmount>",
+    error = "StakingError",
+    mutable,
+    enable_logger
+)]
+fn fund_rewards(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    let params: OnReceivingCis2Params<ContractTokenId, ContractTokenAmount> = ctx.parameter_cursor().get()?;
+
+    // Only accept the configured token
+    let state = host.state_mut();
+    ensure!(ctx.sender().matches_contract(&state.token), StakingError::InvalidToken);
+    ensure!(params.token_id == state.token_id, StakingError::InvalidToken);
+
+    state.reward_pool_balance = checked_add(state.reward_pool_balance, params.amount)?;
+    logger.log(&StakingEvent::RewardsFunded { from: params.from, amount: params.amount })?;
+    Ok(())
+}
+
+
+
+
+
+
+
+/// Function to set the annual reward rate in basis points. The new rate
+/// applies to the accrual of all stakes from now on; rewards accrued so far
+/// are kept.
+#[receive(contract = "gonana_staking_smart_contract", name = "set_reward_rate", parameter = "u64", error = "StakingError", mutable, enable_logger)]
+fn set_reward_rate(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    // Only the admin can change the reward rate
+    host.state().ensure_admin(&ctx.sender())?;
+    let reward_rate_bps: u64 = ctx.parameter_cursor().get()?;
+    let now = ctx.metadata().block_time();
+
+    let state = host.state_mut();
+    state.reward_rate_bps = reward_rate_bps;
+    let tiers: Vec<TierId> = state.lock_tiers.iter().map(|(tier, _)| *tier).collect();
+    state.update_reward_index(None, now)?;
+    for tier in tiers {
+        state.update_reward_index(Some(tier), now)?;
+    }
+    logger.log(&StakingEvent::RateChanged { reward_rate_bps })?;
     Ok(())
 }
 
@@ -268,15 +1366,106 @@ fn release_funds(ctx: &ReceiveContext, host: &mut Host<State>) -> Result<(), Sta
 
 
 
+/// Function to set how long new stakes are locked
+#[receive(contract = "gonana_staking_smart_contract", name = "set_min_lock_duration", parameter = "Duration", error = "StakingError", mutable, enable_logger)]
+fn set_min_lock_duration(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    host.state().ensure_admin(&ctx.sender())?;
+    let min_lock_duration: Duration = ctx.parameter_cursor().get()?;
+
+    host.state_mut().min_lock_duration = min_lock_duration;
+    logger.log(&StakingEvent::MinLockDurationChanged { min_lock_duration })?;
+    Ok(())
+}
+
+
+
+
+/// Function to add or change a lock tier. A changed multiplier applies to the
+/// accrual of all stakes in the tier from now on; its lock duration applies to
+/// new stakes and top-ups.
+#[receive(contract = "gonana_staking_smart_contract", name = "set_lock_tier", parameter = "SetLockTierParams", error = "StakingError", mutable, enable_logger)]
+fn set_lock_tier(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    host.state().ensure_admin(&ctx.sender())?;
+    let params: SetLockTierParams = ctx.parameter_cursor().get()?;
+    let now = ctx.metadata().block_time();
+
+    let state = host.state_mut();
+    state.lock_tiers.insert(params.tier, params.lock_tier.clone());
+    state.update_reward_index(Some(params.tier), now)?;
+    logger.log(&StakingEvent::LockTierSet { tier: params.tier, lock_tier: params.lock_tier })?;
+    Ok(())
+}
+
+
+
+
+/// Function to pause or unpause staking and releases
+#[receive(contract = "gonana_staking_smart_contract", name = "set_paused", parameter = "PauseFlags", error = "StakingError", mutable, enable_logger)]
+fn set_paused(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    host.state().ensure_admin(&ctx.sender())?;
+    let paused: PauseFlags = ctx.parameter_cursor().get()?;
+
+    host.state_mut().paused = paused;
+    logger.log(&StakingEvent::Paused(paused))?;
+    Ok(())
+}
+
+
+
+
+/// Function to propose a new admin. The proposed account becomes admin once it
+/// calls `accept_admin`.
+#[receive(contract = "gonana_staking_smart_contract", name = "transfer_admin", parameter = "AccountAddress", error = "StakingError", mutable, enable_logger)]
+fn transfer_admin(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    host.state().ensure_admin(&ctx.sender())?;
+    let new_admin: AccountAddress = ctx.parameter_cursor().get()?;
+
+    host.state_mut().pending_admin = Some(new_admin);
+    logger.log(&StakingEvent::AdminProposed { pending_admin: new_admin })?;
+    Ok(())
+}
+
+
+
+
+/// Function for the proposed admin to accept the admin role
+#[receive(contract = "gonana_staking_smart_contract", name = "accept_admin", error = "StakingError", mutable, enable_logger)]
+fn accept_admin(ctx: &ReceiveContext, host: &mut Host<State>, logger: &mut Logger) -> Result<(), StakingError> {
+    let state = host.state_mut();
+    let pending_admin = state.pending_admin.ok_or(StakingError::Unauthorized)?;
+    ensure!(ctx.sender().matches_account(&pending_admin), StakingError::Unauthorized);
+
+    state.admin = pending_admin;
+    state.pending_admin = None;
+    logger.log(&StakingEvent::AdminChanged { admin: pending_admin })?;
+    Ok(())
+}
+
+
+
+
+/// Function to view the current admin
+#[receive(contract = "gonana_staking_smart_contract", name = "view_admin", return_value = "AccountAddress", error = "StakingError")]
+fn view_admin(_ctx: &ReceiveContext, host: &Host<State>) -> Result<AccountAddress, StakingError> {
+    Ok(host.state().admin)
+}
+
+
+
+
+
+
+
 /// Function to get stake information by ID
 #[receive(
     contract = "gonana_staking_smart_contract",
     name = "get_stake_info",
-    parameter = "AccountAddress",
-    return_value = "Option<StakeEntry>"
+    parameter = "StakeId",
+    return_value = "Option<StakeEntry>",
+    error = "StakingError"
 )]
-fn get_stake_info(ctx: &ReceiveContext, host: &Host<State>) -> ReceiveResult<Option<StakeEntry>>{
-    let param : AccountAddress= ctx.parameter_cursor().get()?;
+fn get_stake_info(ctx: &ReceiveContext, host: &Host<State>) -> Result<Option<StakeEntry>, StakingError> {
+    let param : StakeId = ctx.parameter_cursor().get()?;
     
       let stake_entry_ref = host.state().stake_entries.get(&param);
 
@@ -289,25 +1478,186 @@ fn get_stake_info(ctx: &ReceiveContext, host: &Host<State>) -> ReceiveResult<Opt
 
 
 
+/// Function to preview the rewards of the active positions of a staker, at
+/// the current time or a later one, without reimplementing the reward math
+#[receive(
+    contract = "gonana_staking_smart_contract",
+    name = "view_pending_rewards",
+    parameter = "ViewPendingRewardsParams",
+    return_value = "Vec<PendingRewards>",
+    error = "StakingError"
+)]
+fn view_pending_rewards(ctx: &ReceiveContext, host: &Host<State>) -> Result<Vec<PendingRewards>, StakingError> {
+    let param: ViewPendingRewardsParams = ctx.parameter_cursor().get()?;
+    let now = ctx.metadata().block_time();
+    let at = param.at.unwrap_or(now);
+    ensure!(at >= now, StakingError::InvalidTimeOrder);
+
+    let state = host.state();
+    let stake_ids = match param.stake_id {
+        Some(stake_id) => vec![stake_id],
+        None => state.account_stakes.get(&param.staker)
+            .map(|stakes| stakes.iter().map(|stake_id| *stake_id).collect())
+            .unwrap_or_default(),
+    };
+
+    let mut pending_rewards = Vec::with_capacity(stake_ids.len());
+    for stake_id in stake_ids {
+        let stake_entry = state.active_stake(stake_id, param.staker)?;
+        let reward_index = state.reward_index(stake_entry.tier)?;
+        let release_payout = if now >= stake_entry.release_time {
+            checked_add(stake_entry.amount, stake_entry.rewards_at(&reward_index, now)?.min(state.reward_pool_balance))?
+        } else {
+            TokenAmountU64(0)
+        };
+        pending_rewards.push(PendingRewards {
+            stake_id,
+            principal: stake_entry.amount,
+            accrued_reward: stake_entry.rewards_at(&reward_index, at)?,
+            release_time: stake_entry.release_time,
+            release_payout,
+        });
+    }
+
+    Ok(pending_rewards)
+}
+
+
+
+
+/// Function to view aggregate figures of the staking pool
+#[receive(contract = "gonana_staking_smart_contract", name = "view_pool_stats", return_value = "PoolStats", error = "StakingError")]
+fn view_pool_stats(_ctx: &ReceiveContext, host: &Host<State>) -> Result<PoolStats, StakingError> {
+    let state = host.state();
+    Ok(PoolStats {
+        total_staked: state.total_staked,
+        active_stakers: state.active_stakers,
+        total_rewards_paid: state.total_rewards_paid,
+        reward_rate_bps: state.reward_rate_bps,
+        reward_pool_balance: state.reward_pool_balance,
+    })
+}
+
 
 
 
+/// Function to list all positions page by page, in order of their IDs. A page
+/// covers `limit` consecutive IDs, so a filtered page can hold fewer positions.
+#[receive(
+    contract = "gonana_staking_smart_contract",
+    name = "view_stakes",
+    parameter = "ViewStakesParams",
+    return_value = "StakesPage",
+    error = "StakingError"
+)]
+fn view_stakes(ctx: &ReceiveContext, host: &Host<State>) -> Result<StakesPage, StakingError> {
+    let param: ViewStakesParams = ctx.parameter_cursor().get()?;
+    ensure!((1..=MAX_STAKES_PAGE_LIMIT).contains(&param.limit), StakingError::InvalidPageLimit);
+
+    // Position IDs are handed out in order starting from 1
+    let state = host.state();
+    let start = param.cursor.unwrap_or(1);
+    let end = start.saturating_add(u64::from(param.limit)).min(state.next_stake_id);
+    let stakes = (start..end)
+        .filter_map(|stake_id| state.stake_entries.get(&stake_id).map(|entry| (stake_id, entry.clone())))
+        .filter(|(_, entry)| param.state.as_ref().is_none_or(|stake_state| entry.state == *stake_state))
+        .collect();
+    let next_cursor = if end < state.next_stake_id { Some(end) } else { None };
+
+    Ok(StakesPage { stakes, next_cursor })
+}
+
 
 
 
-//function to upgrade contract
+/// Function to view the lock tiers stakers can choose from
 #[receive(
-    contract = "my_contract",
+    contract = "gonana_staking_smart_contract",
+    name = "view_lock_tiers",
+    return_value = "Vec<(TierId, LockTier)>",
+    error = "StakingError"
+)]
+fn view_lock_tiers(_ctx: &ReceiveContext, host: &Host<State>) -> Result<Vec<(TierId, LockTier)>, StakingError> {
+    let lock_tiers = host.state().lock_tiers.iter()
+        .map(|(tier, lock_tier)| (*tier, lock_tier.clone()))
+        .collect();
+
+    Ok(lock_tiers)
+}
+
+
+
+
+/// Function to get the IDs of the active positions of a staker
+#[receive(
+    contract = "gonana_staking_smart_contract",
+    name = "get_account_stakes",
+    parameter = "Address",
+    return_value = "Vec<StakeId>",
+    error = "StakingError"
+)]
+fn get_account_stakes(ctx: &ReceiveContext, host: &Host<State>) -> Result<Vec<StakeId>, StakingError> {
+    let param : Address = ctx.parameter_cursor().get()?;
+
+    let stake_ids = host.state().account_stakes.get(&param)
+        .map(|stakes| stakes.iter().map(|stake_id| *stake_id).collect())
+        .unwrap_or_default();
+
+    Ok(stake_ids)
+}
+
+
+
+
+/// Function to get the IDs of the closed positions of a staker
+#[receive(
+    contract = "gonana_staking_smart_contract",
+    name = "get_closed_stakes",
+    parameter = "Address",
+    return_value = "Vec<StakeId>",
+    error = "StakingError"
+)]
+fn get_closed_stakes(ctx: &ReceiveContext, host: &Host<State>) -> Result<Vec<StakeId>, StakingError> {
+    let param : Address = ctx.parameter_cursor().get()?;
+
+    let stake_ids = host.state().closed_stakes.get(&param)
+        .map(|stakes| stakes.iter().map(|stake_id| *stake_id).collect())
+        .unwrap_or_default();
+
+    Ok(stake_ids)
+}
+
+
+
+
+
+
+
+
+
+
+/// Function to upgrade the contract to a new module. Only the admin can
+/// upgrade. If a migration is given, it is called on the new module right
+/// after the upgrade, and the upgrade is rolled back if it fails. The admin is
+/// read from the fields shared by all layouts of the state, so an upgrade
+/// without the migration it needed can be followed by one with it.
+#[receive(
+    contract = "gonana_staking_smart_contract",
     name = "upgrade",
     parameter = "UpgradeParams",
+    error = "StakingError",
     low_level
 )]
 fn contract_upgrade(
     ctx: &ReceiveContext,
     host: &mut LowLevelHost,
-) -> ReceiveResult<()> {
-    // Check that only the owner is authorized to upgrade the smart contract.
-    ensure!(ctx.sender().matches_account(&ctx.owner()));
+) -> Result<(), StakingError> {
+    // Check that only the admin is authorized to upgrade the smart contract.
+    // Read the admin from the fields shared by all layouts, so that a state
+    // left in an earlier layout can still be upgraded with its migration
+    let mut root = host.state().lookup_entry(&[]).ok_or(StakingError::InvalidStakingState)?;
+    let state = StateV0::deserial_with_state(host.state(), &mut root)?;
+    ensure!(ctx.sender().matches_account(&state.admin), StakingError::Unauthorized);
     // Parse the parameter.
     let params: UpgradeParams = ctx.parameter_cursor().get()?;
     // Trigger the upgrade.
@@ -325,6 +1675,82 @@ fn contract_upgrade(
 }
 
 
+
+
+/// Function to migrate the state from the layout of an earlier module to the
+/// current one. Called by `upgrade` as its migration; it cannot be called
+/// directly, and fails if the state is already in the current layout.
+/// Its cost grows with the number of active positions, which it rewrites;
+/// closed positions are left as they are, so they do not add to it.
+#[receive(contract = "gonana_staking_smart_contract", name = "migrate", error = "StakingError", low_level)]
+fn migrate(ctx: &ReceiveContext, host: &mut LowLevelHost) -> Result<(), StakingError> {
+    ensure!(ctx.sender().matches_contract(&ctx.self_address()), StakingError::Unauthorized);
+
+    // Read the fields shared by all layouts, followed by the version of the
+    // layout, which is missing before version 1
+    let mut root = host.state().lookup_entry(&[]).ok_or(StakingError::InvalidStakingState)?;
+    let old_state = StateV0::deserial_with_state(host.state(), &mut root)?;
+    let size = root.size().map_err(|_| StakingError::InvalidStakingState)?;
+    let state_version = if root.cursor_position() == size { 0 } else { u32::deserial(&mut root)? };
+    // Only migrate earlier layouts, which end with the version
+    ensure!(state_version < STATE_VERSION, StakingError::InvalidStakingState);
+
+    // Version 2 counts the stakers with active positions. Rewards paid are
+    // counted from the migration on.
+    let (active_stakers, total_rewards_paid) = if state_version >= 2 {
+        (u64::deserial(&mut root)?, TokenAmountU64::deserial(&mut root)?)
+    } else {
+        let active_stakers = old_state.account_stakes.iter().filter(|(_, stakes)| has_stakes(stakes)).count() as u64;
+        (active_stakers, TokenAmountU64(0))
+    };
+    ensure!(root.cursor_position() == size, StakingError::InvalidStakingState);
+    let stake_ids: Vec<StakeId> = old_state.account_stakes.iter()
+        .flat_map(|(_, stakes)| stakes.iter().map(|stake_id| *stake_id).collect::<Vec<_>>())
+        .collect();
+    let reward_indexes = StateBuilder::open(host.state().clone()).new_map();
+
+    let mut state = State {
+        stake_entries: old_state.stake_entries,
+        account_stakes: old_state.account_stakes,
+        closed_stakes: old_state.closed_stakes,
+        next_stake_id: old_state.next_stake_id,
+        token: old_state.token,
+        token_id: old_state.token_id,
+        reward_rate_bps: old_state.reward_rate_bps,
+        admin: old_state.admin,
+        pending_admin: old_state.pending_admin,
+        paused: old_state.paused,
+        min_lock_duration: old_state.min_lock_duration,
+        lock_tiers: old_state.lock_tiers,
+        total_staked: old_state.total_staked,
+        reward_pool_balance: old_state.reward_pool_balance,
+        payout_entrypoints: old_state.payout_entrypoints,
+        state_version: STATE_VERSION,
+        active_stakers,
+        total_rewards_paid,
+        reward_indexes,
+    };
+
+    // Version 3 tracks a reward index per rate class and position. Rates
+    // used to apply to the whole life of a position, so the indexes start as
+    // if the current rates applied since the epoch. Only active positions
+    // accrue, so closed ones keep their old layout.
+    let now = ctx.metadata().block_time();
+    let tiers: Vec<TierId> = state.lock_tiers.iter().map(|(tier, _)| *tier).collect();
+    state.update_reward_index(None, now)?;
+    for tier in tiers {
+        state.update_reward_index(Some(tier), now)?;
+    }
+    for stake_id in stake_ids {
+        let mut entry = state.stake_entries.get(&stake_id).map(|entry| entry.clone()).ok_or(StakingError::InvalidStakingState)?;
+        entry.reward_index = RewardIndex::new(state.reward_rate_bps(entry.tier)?, entry.accrual_start).accumulated;
+        state.stake_entries.insert(stake_id, entry);
+    }
+    host.state_mut().write_root(&state);
+    Ok(())
+}
+
+
 //Module successfully deployed with reference: '2eadfae54e3f063c5bda0a27129390c0dd8ebdb2f3196edb0b0d3743f9bdb5ee'.
 //Module reference 2eadfae54e3f063c5bda0a27129390c0dd8ebdb2f3196edb0b0d3743f9bdb5ee was successfully named 'gonana_staking__module'.
 //Module successfully deployed with reference: 'b2584adc2a4fec426cb16ee891fb0183525628412f8209acec2d32d0e0c2f2b1'.
@@ -0,0 +1,185 @@
+//! Contracts the staking contract talks to in the integration tests.
+//!
+//! - `cis2_test_token` is a single-token CIS-2 contract with the
+//!   `approve`/`transfer_from` pair of the Gona token, and a `mint` anyone can
+//!   call.
+//! - `test_wallet` is a contract staker. It forwards calls to other contracts
+//!   and accepts token transfers on `onReceivingCIS2` and `receive_payout`.
+//!
+//! The module is prebuilt in `tests/fixtures`, see the README there.
+#![cfg_attr(not(feature = "std"), no_std)]
+use concordium_cis2::*;
+use concordium_std::*;
+
+/// The only token of the test token contract, the empty token ID.
+type ContractTokenId = TokenIdVec;
+
+type ContractTokenAmount = TokenAmountU64;
+
+#[derive(Serial, DeserialWithState)]
+#[concordium(state_parameter = "S")]
+struct TokenState<S = StateApi> {
+    balances:   StateMap<Address, ContractTokenAmount, S>,
+    /// Allowances by owner and spender.
+    allowances: StateMap<(Address, Address), ContractTokenAmount, S>,
+}
+
+#[derive(Debug, PartialEq, Eq, Reject, Serialize, SchemaType)]
+enum TokenError {
+    ParseParams,
+    InvalidTokenId,
+    InsufficientFunds,
+    Unauthorized,
+    InvokeContractError,
+}
+
+impl From<ParseError> for TokenError {
+    fn from(_: ParseError) -> Self { TokenError::ParseParams }
+}
+
+impl<T> From<CallContractError<T>> for TokenError {
+    fn from(_: CallContractError<T>) -> Self { TokenError::InvokeContractError }
+}
+
+/// Parameter of `mint`.
+#[derive(Serialize, SchemaType)]
+pub struct MintParams {
+    pub owner:  Address,
+    pub amount: ContractTokenAmount,
+}
+
+/// Parameter of `approve`, serialized like the one of the Gona token.
+#[derive(Serialize, SchemaType)]
+pub struct ApproveParams {
+    pub amount:   ContractTokenAmount,
+    pub spender:  Address,
+    pub token_id: ContractTokenId,
+}
+
+/// Parameter of `transfer_from`, serialized like the one of the Gona token.
+#[derive(Serialize, SchemaType)]
+pub struct SpendParams {
+    pub amount:   ContractTokenAmount,
+    pub owner:    Address,
+    pub token_id: ContractTokenId,
+}
+
+impl<S: HasStateApi> TokenState<S> {
+    fn debit(&mut self, owner: &Address, amount: ContractTokenAmount) -> Result<(), TokenError> {
+        let mut balance = self.balances.entry(*owner).or_insert(ContractTokenAmount::from(0));
+        ensure!(*balance >= amount, TokenError::InsufficientFunds);
+        *balance -= amount;
+        Ok(())
+    }
+
+    fn credit(&mut self, owner: &Address, amount: ContractTokenAmount) {
+        *self.balances.entry(*owner).or_insert(ContractTokenAmount::from(0)) += amount;
+    }
+}
+
+fn ensure_token_id(token_id: &ContractTokenId) -> Result<(), TokenError> {
+    ensure!(token_id.0.is_empty(), TokenError::InvalidTokenId);
+    Ok(())
+}
+
+#[init(contract = "cis2_test_token")]
+fn token_init(_ctx: &InitContext, state_builder: &mut StateBuilder) -> InitResult<TokenState> {
+    Ok(TokenState { balances: state_builder.new_map(), allowances: state_builder.new_map() })
+}
+
+/// Creates `amount` new tokens for `owner`.
+#[receive(contract = "cis2_test_token", name = "mint", parameter = "MintParams", error = "TokenError", mutable)]
+fn token_mint(ctx: &ReceiveContext, host: &mut Host<TokenState>) -> Result<(), TokenError> {
+    let params: MintParams = ctx.parameter_cursor().get()?;
+    host.state_mut().credit(&params.owner, params.amount);
+    Ok(())
+}
+
+/// CIS-2 `transfer` of the sender's own tokens. Calls the receive hook of
+/// contract receivers.
+#[receive(contract = "cis2_test_token", name = "transfer", parameter = "TransferParams<ContractTokenId, ContractTokenAmount>", error = "TokenError", mutable)]
+fn token_transfer(ctx: &ReceiveContext, host: &mut Host<TokenState>) -> Result<(), TokenError> {
+    let TransferParams(transfers): TransferParams<ContractTokenId, ContractTokenAmount> = ctx.parameter_cursor().get()?;
+    for Transfer { token_id, amount, from, to, data } in transfers {
+        ensure_token_id(&token_id)?;
+        ensure!(from == ctx.sender(), TokenError::Unauthorized);
+        host.state_mut().debit(&from, amount)?;
+        host.state_mut().credit(&to.address(), amount);
+
+        if let Receiver::Contract(address, entrypoint) = to {
+            let params = OnReceivingCis2Params { token_id, amount, from, data };
+            host.invoke_contract(&address, &params, entrypoint.as_entrypoint_name(), Amount::zero())?;
+        }
+    }
+    Ok(())
+}
+
+/// Allows `spender` to take up to `amount` of the sender's tokens with
+/// `transfer_from`.
+#[receive(contract = "cis2_test_token", name = "approve", parameter = "ApproveParams", error = "TokenError", mutable)]
+fn token_approve(ctx: &ReceiveContext, host: &mut Host<TokenState>) -> Result<(), TokenError> {
+    let params: ApproveParams = ctx.parameter_cursor().get()?;
+    ensure_token_id(&params.token_id)?;
+    host.state_mut().allowances.insert((ctx.sender(), params.spender), params.amount);
+    Ok(())
+}
+
+/// Moves `amount` of the tokens of `owner` to the sender, within the allowance
+/// of the sender.
+#[receive(contract = "cis2_test_token", name = "transfer_from", parameter = "SpendParams", error = "TokenError", mutable)]
+fn token_transfer_from(ctx: &ReceiveContext, host: &mut Host<TokenState>) -> Result<(), TokenError> {
+    let params: SpendParams = ctx.parameter_cursor().get()?;
+    ensure_token_id(&params.token_id)?;
+    let spender = ctx.sender();
+    {
+        let mut allowance = host
+            .state_mut()
+            .allowances
+            .get_mut(&(params.owner, spender))
+            .ok_or(TokenError::Unauthorized)?;
+        ensure!(*allowance >= params.amount, TokenError::Unauthorized);
+        *allowance -= params.amount;
+    }
+    host.state_mut().debit(&params.owner, params.amount)?;
+    host.state_mut().credit(&spender, params.amount);
+    Ok(())
+}
+
+/// CIS-2 `balanceOf`.
+#[receive(contract = "cis2_test_token", name = "balanceOf", parameter = "BalanceOfQueryParams<ContractTokenId>", return_value = "BalanceOfQueryResponse<ContractTokenAmount>", error = "TokenError")]
+fn token_balance_of(ctx: &ReceiveContext, host: &Host<TokenState>) -> Result<BalanceOfQueryResponse<ContractTokenAmount>, TokenError> {
+    let params: BalanceOfQueryParams<ContractTokenId> = ctx.parameter_cursor().get()?;
+    let mut response = Vec::with_capacity(params.queries.len());
+    for query in params.queries {
+        ensure_token_id(&query.token_id)?;
+        response.push(host.state().balances.get(&query.address).map_or(ContractTokenAmount::from(0), |balance| *balance));
+    }
+    Ok(BalanceOfQueryResponse::from(response))
+}
+
+/// Parameter of `forward`: the contract, entrypoint and parameter to call.
+#[derive(Serialize, SchemaType)]
+pub struct ForwardParams {
+    pub contract:   ContractAddress,
+    pub entrypoint: OwnedEntrypointName,
+    pub parameter:  OwnedParameter,
+}
+
+#[init(contract = "test_wallet")]
+fn wallet_init(_ctx: &InitContext, _state_builder: &mut StateBuilder) -> InitResult<()> { Ok(()) }
+
+/// Calls another contract with the wallet as sender.
+#[receive(contract = "test_wallet", name = "forward", parameter = "ForwardParams", mutable)]
+fn wallet_forward(ctx: &ReceiveContext, host: &mut Host<()>) -> ReceiveResult<()> {
+    let params: ForwardParams = ctx.parameter_cursor().get()?;
+    host.invoke_contract_raw(&params.contract, params.parameter.as_parameter(), params.entrypoint.as_entrypoint_name(), Amount::zero())?;
+    Ok(())
+}
+
+/// Accepts token transfers.
+#[receive(contract = "test_wallet", name = "onReceivingCIS2")]
+fn wallet_on_receiving_cis2(_ctx: &ReceiveContext, _host: &Host<()>) -> ReceiveResult<()> { Ok(()) }
+
+/// Accepts token transfers, used as the chosen payout entrypoint.
+#[receive(contract = "test_wallet", name = "receive_payout")]
+fn wallet_receive_payout(_ctx: &ReceiveContext, _host: &Host<()>) -> ReceiveResult<()> { Ok(()) }
@@ -1,83 +1,1316 @@
-// use concordium_smart_contract_testing::*;
-// use gonana_staking_smart_contract::*;
-
-// /// A test account.
-// const ALICE: AccountAddress = AccountAddress([0u8; 32]);
-// const ALICE_ADDR: Address = Address::Account(ALICE);
-
-// /// The initial balance of the ALICE test account.
-// const ACC_INITIAL_BALANCE: Amount = Amount::from_ccd(10_000);
-
-// /// A [`Signer`] with one set of keys, used for signing transactions.
-// const SIGNER: Signer = Signer::with_one_key();
-
-// /// Test that invoking the `receive` endpoint with the `false` parameter
-// /// succeeds in updating the contract.
-// #[test]
-// fn test_throw_no_error() {
-//     let (mut chain, init) = initialize();
-
-//     // Update the contract via the `receive` entrypoint with the parameter `false`.
-//     chain
-//         .contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), UpdateContractPayload {
-//             address:      init.contract_address,
-//             amount:       Amount::zero(),
-//             receive_name: OwnedReceiveName::new_unchecked("gonana_staking_smart_contract.receive".to_string()),
-//             message:      OwnedParameter::from_serial(&false)
-//                 .expect("Parameter within size bounds"),
-//         })
-//         .expect("Update succeeds with `false` as input.");
-// }
-
-// /// Test that invoking the `receive` endpoint with the `true` parameter
-// /// results in the `YourError` being thrown.
-// #[test]
-// fn test_throw_error() {
-//     let (mut chain, init) = initialize();
-
-//     // Update the contract via the `receive` entrypoint with the parameter `true`.
-//     let update = chain
-//         .contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10_000), UpdateContractPayload {
-//             address:      init.contract_address,
-//             amount:       Amount::zero(),
-//             receive_name: OwnedReceiveName::new_unchecked("gonana_staking_smart_contract.receive".to_string()),
-//             message:      OwnedParameter::from_serial(&true).expect("Parameter within size bounds"),
-//         })
-//         .expect_err("Update fails with `true` as input.");
-
-//     // Check that the contract returned `YourError`.
-//     let error: Error = update.parse_return_value().expect("Deserialize `Error`");
-//     assert_eq!(error, Error::YourError);
-// }
-
-// /// Helper method for initializing the contract.
-// ///
-// /// Does the following:
-// ///  - Creates the [`Chain`]
-// ///  - Creates one account, `Alice` with `10_000` CCD as the initial balance.
-// ///  - Initializes the contract.
-// ///  - Returns the [`Chain`] and the [`ContractInitSuccess`]
-// fn initialize() -> (Chain, ContractInitSuccess) {
-//     // Initialize the test chain.
-//     let mut chain = Chain::new();
-
-//     // Create the test account.
-//     chain.create_account(Account::new(ALICE, ACC_INITIAL_BALANCE));
-
-//     // Load the module.
-//     let module = module_load_v1("./concordium-out/module.wasm.v1").expect("Module exists at path");
-//     // Deploy the module.
-//     let deployment = chain.module_deploy_v1(SIGNER, ALICE, module).expect("Deploy valid module");
-
-//     // Initialize the contract.
-//     let init = chain
-//         .contract_init(SIGNER, ALICE, Energy::from(10_000), InitContractPayload {
-//             amount:    Amount::zero(),
-//             mod_ref:   deployment.module_reference,
-//             init_name: OwnedContractName::new_unchecked("init_gonana_staking_smart_contract".to_string()),
-//             param:     OwnedParameter::empty(),
-//         })
-//         .expect("Initializing contract");
-
-//     (chain, init)
-// }
+use concordium_cis2::{
+    AdditionalData, BalanceOfQuery, BalanceOfQueryParams, BalanceOfQueryResponse, OnReceivingCis2Params, Receiver,
+    TokenAmountU64, TokenIdUnit, TokenIdVec, Transfer, TransferParams,
+};
+use concordium_std::{Deserial, Serial};
+use concordium_smart_contract_testing::*;
+use gonana_staking_smart_contract::*;
+
+/// 10% APY.
+const TEN_PERCENT_BPS: u64 = 1_000;
+
+/// A full 365 day year.
+const ONE_YEAR: Duration = Duration::from_days(365);
+
+/// Staking for a full year pays exactly the annual rate.
+#[test]
+fn test_reward_for_one_year() {
+    let reward = calculate_reward(TokenAmountU64(1_000), TEN_PERCENT_BPS, ONE_YEAR);
+    assert_eq!(reward, Ok(TokenAmountU64(100)));
+}
+
+/// Rewards accrue linearly with the time staked.
+#[test]
+fn test_reward_proportional_to_duration() {
+    let half_year = Duration::from_millis(ONE_YEAR.millis() / 2);
+    assert_eq!(calculate_reward(TokenAmountU64(1_000), TEN_PERCENT_BPS, half_year), Ok(TokenAmountU64(50)));
+    // 365% APY pays 1% of the stake per day.
+    assert_eq!(calculate_reward(TokenAmountU64(1_000), 36_500, Duration::from_days(1)), Ok(TokenAmountU64(10)));
+}
+
+/// Rewards accrue linearly with the amount staked.
+#[test]
+fn test_reward_proportional_to_amount() {
+    let small = calculate_reward(TokenAmountU64(1_000), TEN_PERCENT_BPS, ONE_YEAR).unwrap();
+    let large = calculate_reward(TokenAmountU64(1_000_000_000), TEN_PERCENT_BPS, ONE_YEAR).unwrap();
+    assert_eq!(large, TokenAmountU64(small.0 * 1_000_000));
+}
+
+/// Fractional rewards are rounded down to the nearest token unit.
+#[test]
+fn test_reward_rounds_down() {
+    // 1 unit at 10% for a year accrues 0.1 units.
+    assert_eq!(calculate_reward(TokenAmountU64(1), TEN_PERCENT_BPS, ONE_YEAR), Ok(TokenAmountU64(0)));
+    // 1_000 units at 10% for 1 second accrues 0.00000317 units.
+    assert_eq!(calculate_reward(TokenAmountU64(1_000), TEN_PERCENT_BPS, Duration::from_seconds(1)), Ok(TokenAmountU64(0)));
+    // 999 units at 10% for a year accrues 99.9 units.
+    assert_eq!(calculate_reward(TokenAmountU64(999), TEN_PERCENT_BPS, ONE_YEAR), Ok(TokenAmountU64(99)));
+}
+
+/// Nothing accrues without a rate, a duration or a stake.
+#[test]
+fn test_reward_zero_inputs() {
+    assert_eq!(calculate_reward(TokenAmountU64(1_000), 0, ONE_YEAR), Ok(TokenAmountU64(0)));
+    assert_eq!(calculate_reward(TokenAmountU64(1_000), TEN_PERCENT_BPS, Duration::from_millis(0)), Ok(TokenAmountU64(0)));
+    assert_eq!(calculate_reward(TokenAmountU64(0), TEN_PERCENT_BPS, ONE_YEAR), Ok(TokenAmountU64(0)));
+}
+
+/// The largest stake still pays out when the reward fits in a `u64`.
+#[test]
+fn test_reward_max_amount() {
+    // 100% APY for a year pays a reward equal to the stake.
+    assert_eq!(calculate_reward(TokenAmountU64(u64::MAX), BASIS_POINTS, ONE_YEAR), Ok(TokenAmountU64(u64::MAX)));
+}
+
+/// A reward that does not fit in a `u64` is an error rather than wrapping.
+#[test]
+fn test_reward_overflow() {
+    assert_eq!(
+        calculate_reward(TokenAmountU64(u64::MAX), 2 * BASIS_POINTS, ONE_YEAR),
+        Err(StakingError::Overflow)
+    );
+    // The intermediate product exceeds 128 bits.
+    assert_eq!(
+        calculate_reward(TokenAmountU64(u64::MAX), u64::MAX, Duration::from_millis(u64::MAX)),
+        Err(StakingError::Overflow)
+    );
+}
+
+/// Adding amounts succeeds up to `u64::MAX` and fails beyond it.
+#[test]
+fn test_checked_add_boundary() {
+    assert_eq!(checked_add(TokenAmountU64(u64::MAX - 1), TokenAmountU64(1)), Ok(TokenAmountU64(u64::MAX)));
+    assert_eq!(checked_add(TokenAmountU64(u64::MAX), TokenAmountU64(1)), Err(StakingError::Overflow));
+}
+
+/// Elapsed time is zero for equal timestamps and an error when reversed.
+#[test]
+fn test_elapsed_time_ordering() {
+    let earlier = Timestamp::from_timestamp_millis(1_000);
+    let later = Timestamp::from_timestamp_millis(2_000);
+    assert_eq!(elapsed(earlier, later), Ok(Duration::from_millis(1_000)));
+    assert_eq!(elapsed(later, later), Ok(Duration::from_millis(0)));
+    assert_eq!(elapsed(later, earlier), Err(StakingError::InvalidTimeOrder));
+}
+
+/// A reward pool that covers every reward.
+const FULL_POOL: TokenAmountU64 = TokenAmountU64(u64::MAX);
+
+/// 1% of the stake per day.
+const ONE_PERCENT_A_DAY_BPS: u64 = 36_500;
+
+/// A new stake of `amount` opened at the epoch with the default lock terms.
+fn new_stake(amount: u64) -> StakeEntry {
+    let now = day(0);
+    StakeEntry {
+        staker: Address::Account(AccountAddress([0u8; 32])),
+        amount: TokenAmountU64(amount),
+        tier: None,
+        accrual_start: now,
+        reward_index: 0,
+        pending_rewards: TokenAmountU64(0),
+        release_time: now,
+        auto_compound: false,
+        state: StakeEntryState::Active,
+    }
+}
+
+/// The reward index of a rate class that always had `reward_rate_bps`.
+fn fixed_rate(reward_rate_bps: u64) -> RewardIndex { RewardIndex::new(reward_rate_bps, day(0)) }
+
+/// Timestamp `days` days after the epoch.
+fn day(days: u64) -> Timestamp { Timestamp::from_timestamp_millis(Duration::from_days(days).millis()) }
+
+/// Rewards accrued before a top-up are kept and paid out later.
+#[test]
+fn test_top_up_keeps_accrued_rewards() {
+    let mut stake = new_stake(1_000);
+    stake.top_up(TokenAmountU64(1_000), &fixed_rate(TEN_PERCENT_BPS), day(365), FULL_POOL).unwrap();
+    assert_eq!(stake.amount, TokenAmountU64(2_000));
+    assert_eq!(stake.pending_rewards, TokenAmountU64(100));
+
+    // 100 for the first year on 1_000 plus 200 for the second year on 2_000.
+    assert_eq!(stake.take_rewards(&fixed_rate(TEN_PERCENT_BPS), day(730), FULL_POOL), Ok(TokenAmountU64(300)));
+    assert_eq!(stake.pending_rewards, TokenAmountU64(0));
+    assert_eq!(stake.accrual_start, day(730));
+}
+
+/// Daily top-ups pay exactly the reward of each day's balance.
+#[test]
+fn test_repeated_top_ups_lose_no_rewards() {
+    let mut stake = new_stake(100);
+    for days in 1..10 {
+        stake.top_up(TokenAmountU64(100), &fixed_rate(ONE_PERCENT_A_DAY_BPS), day(days), FULL_POOL).unwrap();
+    }
+    assert_eq!(stake.amount, TokenAmountU64(1_000));
+
+    // 1% of 100, 200, ..., 1_000 for one day each.
+    let expected: u64 = (1..=10).sum();
+    assert_eq!(stake.take_rewards(&fixed_rate(ONE_PERCENT_A_DAY_BPS), day(10), FULL_POOL), Ok(TokenAmountU64(expected)));
+}
+
+/// Irregular top-ups pay the sum of the rewards of each period between them.
+#[test]
+fn test_irregular_top_ups_match_periods() {
+    let top_ups = [(day(3), 250), (day(40), 1_337), (day(41), 5), (day(41), 7_000), (day(200), 1)];
+
+    let mut stake = new_stake(1_000);
+    let mut expected = TokenAmountU64(0);
+    let mut balance = TokenAmountU64(1_000);
+    let mut since = day(0);
+    for (now, amount) in top_ups {
+        expected += calculate_reward(balance, TEN_PERCENT_BPS, elapsed(since, now).unwrap()).unwrap();
+        stake.top_up(TokenAmountU64(amount), &fixed_rate(TEN_PERCENT_BPS), now, FULL_POOL).unwrap();
+        balance += TokenAmountU64(amount);
+        since = now;
+    }
+    expected += calculate_reward(balance, TEN_PERCENT_BPS, elapsed(since, day(365)).unwrap()).unwrap();
+
+    assert_eq!(stake.amount, balance);
+    assert_eq!(stake.take_rewards(&fixed_rate(TEN_PERCENT_BPS), day(365), FULL_POOL), Ok(expected));
+}
+
+/// Auto-compounding stakes add accrued rewards to the stake on top-up.
+#[test]
+fn test_top_up_compounds_when_auto_compounding() {
+    let mut stake = new_stake(1_000);
+    stake.auto_compound = true;
+    stake.top_up(TokenAmountU64(1_000), &fixed_rate(ONE_PERCENT_A_DAY_BPS), day(1), FULL_POOL).unwrap();
+    assert_eq!(stake.amount, TokenAmountU64(2_010));
+    assert_eq!(stake.pending_rewards, TokenAmountU64(0));
+}
+
+/// Previewing rewards matches what is paid out later and changes nothing.
+#[test]
+fn test_rewards_at_previews_take_rewards() {
+    let mut stake = new_stake(1_000);
+    stake.top_up(TokenAmountU64(1_000), &fixed_rate(ONE_PERCENT_A_DAY_BPS), day(1), FULL_POOL).unwrap();
+    let before = stake.clone();
+
+    // 10 for the first day on 1_000 plus 20 a day on 2_000 after that.
+    assert_eq!(stake.rewards_at(&fixed_rate(ONE_PERCENT_A_DAY_BPS), day(5)), Ok(TokenAmountU64(90)));
+    assert_eq!(stake, before);
+    assert_eq!(stake.take_rewards(&fixed_rate(ONE_PERCENT_A_DAY_BPS), day(5), FULL_POOL), Ok(TokenAmountU64(90)));
+    // No preview before accrual started.
+    assert_eq!(before.rewards_at(&fixed_rate(ONE_PERCENT_A_DAY_BPS), day(0)), Err(StakingError::InvalidTimeOrder));
+}
+
+/// Only the rewards the pool covers are taken; the rest stays owed.
+#[test]
+fn test_take_rewards_up_to_available() {
+    let mut stake = new_stake(1_000);
+    assert_eq!(stake.take_rewards(&fixed_rate(TEN_PERCENT_BPS), day(365), TokenAmountU64(30)), Ok(TokenAmountU64(30)));
+    assert_eq!(stake.pending_rewards, TokenAmountU64(70));
+
+    // Compounding is capped the same way.
+    assert_eq!(stake.compound(&fixed_rate(TEN_PERCENT_BPS), day(365), TokenAmountU64(50)), Ok(TokenAmountU64(50)));
+    assert_eq!((stake.amount, stake.pending_rewards), (TokenAmountU64(1_050), TokenAmountU64(20)));
+}
+
+/// A rate change only applies to the accrual after it.
+#[test]
+fn test_rate_change_applies_from_now() {
+    let mut reward_index = fixed_rate(TEN_PERCENT_BPS);
+    let mut stake = new_stake(1_000);
+    reward_index.set_rate(2 * TEN_PERCENT_BPS, day(365)).unwrap();
+
+    // 100 for the first year at 10% plus 200 for the second year at 20%.
+    assert_eq!(stake.rewards_at(&reward_index, day(730)), Ok(TokenAmountU64(300)));
+    assert_eq!(stake.take_rewards(&reward_index, day(730), FULL_POOL), Ok(TokenAmountU64(300)));
+
+    // From then on the stake accrues at the new rate only.
+    assert_eq!(stake.take_rewards(&reward_index, day(1095), FULL_POOL), Ok(TokenAmountU64(200)));
+}
+
+/// The documented error code of every error.
+const ERROR_CODES: [(StakingError, i32); 20] = [
+    (StakingError::StakingNotFound, -1),
+    (StakingError::InsufficientFunds, -2),
+    (StakingError::InvalidPrice, -3),
+    (StakingError::InvalidReleaseTime, -4),
+    (StakingError::InvalidStakingState, -5),
+    (StakingError::ParseParams, -6),
+    (StakingError::TransferError, -7),
+    (StakingError::ContractInvokeError, -8),
+    (StakingError::Unauthorized, -9),
+    (StakingError::Overflow, -10),
+    (StakingError::InvalidTimeOrder, -11),
+    (StakingError::ContractPaused, -12),
+    (StakingError::InvalidLockTier, -13),
+    (StakingError::RewardPoolExhausted, -14),
+    (StakingError::InvalidToken, -15),
+    (StakingError::InvalidBeneficiary, -16),
+    (StakingError::LogError, -17),
+    (StakingError::UpgradeError, -18),
+    (StakingError::InvalidAmount, -19),
+    (StakingError::InvalidPageLimit, -20),
+];
+
+/// Errors keep their documented error codes, and serialize to the tag matching
+/// their code.
+#[test]
+fn test_error_codes_are_stable() {
+    for (error, code) in ERROR_CODES {
+        assert_eq!(error.error_code(), code, "{error:?}");
+        assert_eq!(to_bytes(&error), vec![(-code - 1) as u8], "{error:?}");
+        assert_eq!(from_bytes(&to_bytes(&error)), Ok(error));
+    }
+}
+
+/// A test account, the admin of the test contract.
+const ALICE: AccountAddress = AccountAddress([0u8; 32]);
+
+/// A test account that is not the admin.
+const BOB: AccountAddress = AccountAddress([1u8; 32]);
+
+/// The initial balance of the test accounts.
+const ACC_INITIAL_BALANCE: Amount = Amount::from_ccd(10_000);
+
+/// A [`Signer`] with one set of keys, used for signing transactions.
+const SIGNER: Signer = Signer::with_one_key();
+
+/// The module built by `cargo concordium build`.
+const MODULE_PATH: &str = "./concordium-out/module.wasm.v1";
+
+/// A module with the unversioned state layout, before `migrate` was added.
+const MODULE_STATE_V0_PATH: &str = "./tests/fixtures/module_state_v0.wasm.v1";
+
+/// A module with version 1 of the state layout, before the pool statistics.
+const MODULE_STATE_V1_PATH: &str = "./tests/fixtures/module_state_v1.wasm.v1";
+
+/// A module with version 2 of the state layout, before the reward indexes.
+const MODULE_STATE_V2_PATH: &str = "./tests/fixtures/module_state_v2.wasm.v1";
+
+/// The module with the test token and the test wallet, see `tests/contracts`.
+const TEST_CONTRACTS_PATH: &str = "./tests/fixtures/test_contracts.wasm.v1";
+
+/// The address of the test token, the first contract of every test chain.
+const TOKEN: ContractAddress = ContractAddress { index: 0, subindex: 0 };
+
+/// The admin upgrades the contract to a new module.
+#[test]
+fn test_upgrade() {
+    let (mut chain, contract) = initialize();
+    let new_module = deploy_new_version(&mut chain);
+
+    upgrade(&mut chain, ALICE, contract, &UpgradeParams { module: new_module, migrate: None })
+        .expect("Admin can upgrade");
+
+    assert_eq!(chain.get_contract(contract).map(|c| c.module_reference), Some(new_module));
+}
+
+/// The migration is called on the new module right after the upgrade.
+#[test]
+fn test_upgrade_calls_migration() {
+    let (mut chain, contract) = initialize();
+    let new_module = deploy_new_version(&mut chain);
+
+    let migrate = (OwnedEntrypointName::new_unchecked("view_admin".to_string()), OwnedParameter::empty());
+    let update = upgrade(&mut chain, ALICE, contract, &UpgradeParams { module: new_module, migrate: Some(migrate) })
+        .expect("Upgrade with migration succeeds");
+
+    // The upgrade is followed by the call to the migration.
+    let trace = update.effective_trace_elements_cloned();
+    let upgraded = trace.iter().position(|element| matches!(element, ContractTraceElement::Upgraded { to, .. } if *to == new_module));
+    let migrated = trace.iter().position(|element| matches!(element,
+        ContractTraceElement::Updated { data } if data.receive_name.as_receive_name().entrypoint_name() == "view_admin"));
+    assert!(upgraded.is_some() && migrated > upgraded);
+}
+
+/// A failing migration rolls back the upgrade.
+#[test]
+fn test_upgrade_rolled_back_on_failed_migration() {
+    let (mut chain, contract) = initialize();
+    let old_module = chain.get_contract(contract).map(|c| c.module_reference);
+    let new_module = deploy_new_version(&mut chain);
+
+    let migrate = (OwnedEntrypointName::new_unchecked("no_such_entrypoint".to_string()), OwnedParameter::empty());
+    upgrade(&mut chain, ALICE, contract, &UpgradeParams { module: new_module, migrate: Some(migrate) })
+        .expect_err("Upgrade with failing migration fails");
+
+    assert_eq!(chain.get_contract(contract).map(|c| c.module_reference), old_module);
+}
+
+/// Only the admin can upgrade the contract.
+#[test]
+fn test_upgrade_only_admin() {
+    let (mut chain, contract) = initialize();
+    let new_module = deploy_new_version(&mut chain);
+
+    let update = upgrade(&mut chain, BOB, contract, &UpgradeParams { module: new_module, migrate: None })
+        .expect_err("Only the admin can upgrade");
+
+    assert_eq!(update.parse_return_value(), Ok(StakingError::Unauthorized));
+}
+
+/// Only the admin can change the settings of the contract or propose a new
+/// admin.
+#[test]
+fn test_settings_only_admin() {
+    let (mut chain, contract) = initialize();
+    let lock_tier = LockTier { lock_duration: Duration::from_days(180), reward_multiplier_bps: 20_000 };
+
+    let results = [
+        ("set_reward_rate", update(&mut chain, BOB, contract, "set_reward_rate", &TEN_PERCENT_BPS)),
+        ("set_paused", update(&mut chain, BOB, contract, "set_paused", &PauseFlags::default())),
+        ("set_lock_tier", update(&mut chain, BOB, contract, "set_lock_tier", &SetLockTierParams { tier: 1, lock_tier })),
+        ("set_min_lock_duration", update(&mut chain, BOB, contract, "set_min_lock_duration", &Duration::from_days(1))),
+        ("transfer_admin", update(&mut chain, BOB, contract, "transfer_admin", &BOB)),
+    ];
+    for (entrypoint, result) in results {
+        let error = result.expect_err(entrypoint);
+        assert_eq!(error.parse_return_value(), Ok(StakingError::Unauthorized), "{entrypoint}");
+    }
+    assert_eq!(view::<AccountAddress>(&chain, contract, "view_admin", &()), ALICE);
+}
+
+/// The admin role moves only once the proposed admin accepts it, and only the
+/// proposed admin can accept.
+#[test]
+fn test_transfer_admin() {
+    let (mut chain, contract) = initialize();
+    let error = update(&mut chain, BOB, contract, "accept_admin", &()).expect_err("Nobody is proposed");
+    assert_eq!(error.parse_return_value(), Ok(StakingError::Unauthorized));
+
+    update(&mut chain, ALICE, contract, "transfer_admin", &BOB).expect("Propose admin");
+    let error = update(&mut chain, ALICE, contract, "accept_admin", &()).expect_err("Not the proposed admin");
+    assert_eq!(error.parse_return_value(), Ok(StakingError::Unauthorized));
+    assert_eq!(view::<AccountAddress>(&chain, contract, "view_admin", &()), ALICE);
+
+    let accepted = update(&mut chain, BOB, contract, "accept_admin", &()).expect("Accept admin");
+    assert_eq!(staking_events(&accepted, contract), vec![StakingEvent::AdminChanged { admin: BOB }]);
+    assert_eq!(view::<AccountAddress>(&chain, contract, "view_admin", &()), BOB);
+    update(&mut chain, BOB, contract, "accept_admin", &()).expect_err("Proposal is used up");
+
+    // The previous admin lost the role, the new one has it.
+    let error = update(&mut chain, ALICE, contract, "set_reward_rate", &TEN_PERCENT_BPS).expect_err("No longer admin");
+    assert_eq!(error.parse_return_value(), Ok(StakingError::Unauthorized));
+    update(&mut chain, BOB, contract, "set_reward_rate", &TEN_PERCENT_BPS).expect("Set reward rate");
+}
+
+/// State written by the unversioned module is migrated during the upgrade.
+#[test]
+fn test_migrate_state_from_v0() { test_migrate_state_from(MODULE_STATE_V0_PATH) }
+
+/// State written by the module with version 1 of the layout is migrated
+/// during the upgrade.
+#[test]
+fn test_migrate_state_from_v1() { test_migrate_state_from(MODULE_STATE_V1_PATH) }
+
+/// State written by the module with version 2 of the layout is migrated
+/// during the upgrade.
+#[test]
+fn test_migrate_state_from_v2() { test_migrate_state_from(MODULE_STATE_V2_PATH) }
+
+/// State written by the old module at `module_path` is migrated during the
+/// upgrade and stays usable by the new module.
+fn test_migrate_state_from(module_path: &str) {
+    let (mut chain, contract) = initialize_module(module_path);
+    let lock_tier = LockTier { lock_duration: Duration::from_days(180), reward_multiplier_bps: 20_000 };
+    update(&mut chain, ALICE, contract, "set_lock_tier", &SetLockTierParams { tier: 1, lock_tier: lock_tier.clone() })
+        .expect("Set lock tier");
+    update(&mut chain, ALICE, contract, "transfer_admin", &BOB).expect("Propose admin");
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    // A position closed before the upgrade.
+    update(&mut chain, ALICE, contract, "set_min_lock_duration", &Duration::from_millis(0)).expect("Set minimum lock duration");
+    stake(&mut chain, contract, BOB, 1_000).expect("Stake");
+    update(&mut chain, BOB, contract, "release_funds", &ReleaseFundsParams { stake_id: 3 }).expect("Release");
+    fund_rewards(&mut chain, contract, 500).expect("Fund rewards");
+
+    let new_module = chain.module_deploy_v1(SIGNER, ALICE, module_load_v1(MODULE_PATH).expect("Module exists at path"))
+        .expect("Deploy valid module").module_reference;
+    upgrade(&mut chain, ALICE, contract, &UpgradeParams { module: new_module, migrate: Some(migration()) })
+        .expect("Upgrade with migration succeeds");
+
+    let lock_tiers: Vec<(TierId, LockTier)> = view(&chain, contract, "view_lock_tiers", &());
+    assert_eq!(lock_tiers, vec![(1, lock_tier)]);
+    // The closed position is left in its old layout and can still be read.
+    let page: StakesPage = view(&chain, contract, "view_stakes", &ViewStakesParams { cursor: None, limit: 10, state: Some(StakeEntryState::Inactive) });
+    assert_eq!(page.stakes.iter().map(|(stake_id, stake_entry)| (*stake_id, stake_entry.staker)).collect::<Vec<_>>(), vec![(3, Address::Account(BOB))]);
+    // The pending admin is kept and can still accept.
+    update(&mut chain, BOB, contract, "accept_admin", &()).expect("Accept admin");
+    assert_eq!(view::<AccountAddress>(&chain, contract, "view_admin", &()), BOB);
+    // The statistics count the existing stakers.
+    assert_eq!(view::<PoolStats>(&chain, contract, "view_pool_stats", &()), PoolStats {
+        total_staked: TokenAmountU64(2_000),
+        active_stakers: 1,
+        total_rewards_paid: TokenAmountU64(0),
+        reward_rate_bps: TEN_PERCENT_BPS,
+        reward_pool_balance: TokenAmountU64(500),
+    });
+    // The positions keep accruing at the rates they had.
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "claim_rewards", &1u64).expect("Claim rewards");
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 100);
+}
+
+/// The pool statistics follow stakes, funding and reward payments.
+#[test]
+fn test_pool_stats() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    stake(&mut chain, contract, ALICE, 2_000).expect("Stake");
+    stake(&mut chain, contract, BOB, 7_000).expect("Stake");
+    fund_rewards(&mut chain, contract, 500).expect("Fund rewards");
+
+    // A year at 10% on the first position of ALICE.
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "compound", &1u64).expect("Compound");
+
+    assert_eq!(view::<PoolStats>(&chain, contract, "view_pool_stats", &()), PoolStats {
+        total_staked: TokenAmountU64(10_100),
+        active_stakers: 2,
+        total_rewards_paid: TokenAmountU64(100),
+        reward_rate_bps: TEN_PERCENT_BPS,
+        reward_pool_balance: TokenAmountU64(400),
+    });
+}
+
+/// Upgrading from the old module without the migration leaves a state the new
+/// module cannot read, until a second upgrade with the migration.
+#[test]
+fn test_upgrade_recovers_from_missing_migration() {
+    let (mut chain, contract) = initialize_module(MODULE_STATE_V0_PATH);
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    fund_rewards(&mut chain, contract, 500).expect("Fund rewards");
+    let new_module = chain.module_deploy_v1(SIGNER, ALICE, module_load_v1(MODULE_PATH).expect("Module exists at path"))
+        .expect("Deploy valid module").module_reference;
+    upgrade(&mut chain, ALICE, contract, &UpgradeParams { module: new_module, migrate: None })
+        .expect("Upgrade succeeds");
+    update(&mut chain, ALICE, contract, "set_reward_rate", &TEN_PERCENT_BPS).expect_err("State cannot be read");
+
+    // Only the admin can still upgrade.
+    let error = upgrade(&mut chain, BOB, contract, &UpgradeParams { module: new_module, migrate: Some(migration()) })
+        .expect_err("Only the admin can upgrade");
+    assert_eq!(error.parse_return_value(), Ok(StakingError::Unauthorized));
+    upgrade(&mut chain, ALICE, contract, &UpgradeParams { module: new_module, migrate: Some(migration()) })
+        .expect("Upgrade with migration succeeds");
+
+    update(&mut chain, ALICE, contract, "set_reward_rate", &TEN_PERCENT_BPS).expect("Set reward rate");
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "release_funds", &ReleaseFundsParams { stake_id: 1 }).expect("Release");
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 1_100);
+}
+
+/// A migrated state is not migrated again.
+#[test]
+fn test_migrate_only_once() {
+    let (mut chain, contract) = initialize();
+    let new_module = deploy_new_version(&mut chain);
+
+    upgrade(&mut chain, ALICE, contract, &UpgradeParams { module: new_module, migrate: Some(migration()) })
+        .expect_err("State is already migrated");
+}
+
+/// The migration can only run as part of an upgrade.
+#[test]
+fn test_migrate_only_by_upgrade() {
+    let (mut chain, contract) = initialize_module(MODULE_STATE_V0_PATH);
+    let new_module = chain.module_deploy_v1(SIGNER, ALICE, module_load_v1(MODULE_PATH).expect("Module exists at path"))
+        .expect("Deploy valid module").module_reference;
+    upgrade(&mut chain, ALICE, contract, &UpgradeParams { module: new_module, migrate: None })
+        .expect("Upgrade succeeds");
+
+    let update = update(&mut chain, ALICE, contract, "migrate", &()).expect_err("Only the contract can migrate");
+    assert_eq!(update.parse_return_value(), Ok(StakingError::Unauthorized));
+}
+
+/// All positions can be listed page by page.
+#[test]
+fn test_view_stakes_pages() {
+    let (mut chain, contract) = initialize();
+    for amount in [100, 200, 300] {
+        stake(&mut chain, contract, ALICE, amount).expect("Stake");
+    }
+
+    let page: StakesPage = view(&chain, contract, "view_stakes", &ViewStakesParams { cursor: None, limit: 2, state: None });
+    assert_eq!(page.stakes.iter().map(|(stake_id, entry)| (*stake_id, entry.amount)).collect::<Vec<_>>(),
+        vec![(1, TokenAmountU64(100)), (2, TokenAmountU64(200))]);
+    assert_eq!(page.next_cursor, Some(3));
+
+    let page: StakesPage = view(&chain, contract, "view_stakes", &ViewStakesParams { cursor: page.next_cursor, limit: 2, state: None });
+    assert_eq!(page.stakes.iter().map(|(stake_id, _)| *stake_id).collect::<Vec<_>>(), vec![3]);
+    assert_eq!(page.next_cursor, None);
+}
+
+/// Pages only hold positions in the requested state.
+#[test]
+fn test_view_stakes_filters_state() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 100).expect("Stake");
+
+    let params = ViewStakesParams { cursor: None, limit: 10, state: Some(StakeEntryState::Active) };
+    let page: StakesPage = view(&chain, contract, "view_stakes", &params);
+    assert_eq!(page.stakes.len(), 1);
+    assert_eq!(page.stakes[0].1.staker, Address::Account(ALICE));
+
+    let params = ViewStakesParams { cursor: None, limit: 10, state: Some(StakeEntryState::Inactive) };
+    let page: StakesPage = view(&chain, contract, "view_stakes", &params);
+    assert_eq!(page, StakesPage { stakes: Vec::new(), next_cursor: None });
+}
+
+/// Pages cover at least one and at most `MAX_STAKES_PAGE_LIMIT` IDs.
+#[test]
+fn test_view_stakes_limit() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 100).expect("Stake");
+
+    for limit in [0, MAX_STAKES_PAGE_LIMIT + 1] {
+        let error = invoke(&chain, contract, "view_stakes", &ViewStakesParams { cursor: None, limit, state: None })
+            .expect_err("Invalid limit");
+        assert_eq!(error.parse_return_value(), Ok(StakingError::InvalidPageLimit));
+    }
+    let page: StakesPage = view(&chain, contract, "view_stakes", &ViewStakesParams { cursor: None, limit: MAX_STAKES_PAGE_LIMIT, state: None });
+    assert_eq!(page.stakes.len(), 1);
+}
+
+/// A failing view returns its error, so callers can decode it.
+#[test]
+fn test_view_returns_typed_error() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 100).expect("Stake");
+
+    let params = ViewPendingRewardsParams { staker: Address::Account(BOB), stake_id: Some(1), at: None };
+    let error = chain
+        .contract_invoke(BOB, Address::Account(BOB), Energy::from(100_000), UpdateContractPayload {
+            address:      contract,
+            amount:       Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked("gonana_staking_smart_contract.view_pending_rewards".to_string()),
+            message:      OwnedParameter::from_serial(&params).expect("Parameter within size bounds"),
+        })
+        .expect_err("Position of another staker");
+
+    assert_eq!(error.parse_return_value(), Ok(StakingError::Unauthorized));
+    assert!(matches!(error.kind, ContractInvokeErrorKind::ExecutionError {
+        failure_kind: InvokeFailure::ContractReject { code: -9, .. }
+    }));
+}
+
+/// The preview pays out nothing while the stake is locked, accrues rewards up
+/// to a later time and rejects a time in the past.
+#[test]
+fn test_view_pending_rewards_at() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    fund_rewards(&mut chain, contract, 1_000).expect("Fund rewards");
+    chain.tick_block_time(Duration::from_days(10)).expect("Block time in range");
+
+    let now = chain.block_time();
+    let at = now.checked_add(Duration::from_days(10)).expect("Time in range");
+    let pending: Vec<PendingRewards> =
+        view(&chain, contract, "view_pending_rewards", &ViewPendingRewardsParams { staker: Address::Account(ALICE), stake_id: None, at: Some(at) });
+    assert_eq!(pending, vec![PendingRewards {
+        stake_id:       1,
+        principal:      TokenAmountU64(1_000),
+        // 20 days at 10% on the principal, rounded down.
+        accrued_reward: TokenAmountU64(5),
+        release_time:   Timestamp::from_timestamp_millis(Duration::from_days(30).millis()),
+        release_payout: TokenAmountU64(0),
+    }]);
+
+    let past = now.checked_sub(Duration::from_millis(1)).expect("Time in range");
+    let error = invoke(&chain, contract, "view_pending_rewards", &ViewPendingRewardsParams { staker: Address::Account(ALICE), stake_id: None, at: Some(past) })
+        .expect_err("Time in the past");
+    assert_eq!(error.parse_return_value(), Ok(StakingError::InvalidTimeOrder));
+}
+
+/// A new reward rate applies to the accrual of existing stakes from the change
+/// on.
+#[test]
+fn test_set_reward_rate_mid_stake() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    fund_rewards(&mut chain, contract, 1_000).expect("Fund rewards");
+
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "set_reward_rate", &(2 * TEN_PERCENT_BPS)).expect("Set reward rate");
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "claim_rewards", &1u64).expect("Claim rewards");
+
+    // 100 for the first year at 10% plus 200 for the second year at 20%.
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 300);
+}
+
+/// A new multiplier of a lock tier applies to the accrual of its stakes from
+/// the change on.
+#[test]
+fn test_set_lock_tier_mid_stake() {
+    let (mut chain, contract) = initialize();
+    let lock_tier = LockTier { lock_duration: Duration::from_days(180), reward_multiplier_bps: 20_000 };
+    update(&mut chain, ALICE, contract, "set_lock_tier", &SetLockTierParams { tier: 1, lock_tier }).expect("Set lock tier");
+    stake_with(&mut chain, contract, ALICE, 1_000, &StakeTransferData { tier: Some(1), ..Default::default() }).expect("Stake");
+    fund_rewards(&mut chain, contract, 1_000).expect("Fund rewards");
+
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    let lock_tier = LockTier { lock_duration: Duration::from_days(180), reward_multiplier_bps: 10_000 };
+    update(&mut chain, ALICE, contract, "set_lock_tier", &SetLockTierParams { tier: 1, lock_tier }).expect("Set lock tier");
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "claim_rewards", &1u64).expect("Claim rewards");
+
+    // 200 for the first year at twice the base rate plus 100 for the second.
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 300);
+}
+
+/// A stake in a lock tier cannot be released before the lock duration of the
+/// tier has passed, even once the minimum lock duration has.
+#[test]
+fn test_release_after_tier_lock() {
+    let (mut chain, contract) = initialize();
+    let lock_tier = LockTier { lock_duration: Duration::from_days(180), reward_multiplier_bps: 20_000 };
+    update(&mut chain, ALICE, contract, "set_lock_tier", &SetLockTierParams { tier: 1, lock_tier }).expect("Set lock tier");
+    stake_with(&mut chain, contract, ALICE, 1_000, &StakeTransferData { tier: Some(1), ..Default::default() }).expect("Stake");
+    fund_rewards(&mut chain, contract, 1_000).expect("Fund rewards");
+
+    // One millisecond before the end of the 180 day lock.
+    chain.tick_block_time(Duration::from_millis(Duration::from_days(180).millis() - 1)).expect("Block time in range");
+    let error = update(&mut chain, ALICE, contract, "release_funds", &ReleaseFundsParams { stake_id: 1 })
+        .expect_err("Release while locked");
+    assert_eq!(error.parse_return_value(), Ok(StakingError::InvalidReleaseTime));
+
+    chain.tick_block_time(Duration::from_millis(1)).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "release_funds", &ReleaseFundsParams { stake_id: 1 }).expect("Release");
+    // The principal and 180 days at twice the base rate on it, rounded down.
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 1_098);
+}
+
+/// Staking in an unknown tier, or topping up a position with another tier
+/// than its own, is rejected.
+#[test]
+fn test_stake_invalid_tier() {
+    let (mut chain, contract) = initialize();
+    let lock_tier = LockTier { lock_duration: Duration::from_days(180), reward_multiplier_bps: 20_000 };
+    update(&mut chain, ALICE, contract, "set_lock_tier", &SetLockTierParams { tier: 1, lock_tier }).expect("Set lock tier");
+    mint(&mut chain, Address::Account(ALICE), 2_000);
+    let approval = ApproveParam { amount: TokenAmountU64(2_000), spender: Address::Contract(contract), token_id: TokenIdUnit() };
+    call(&mut chain, ALICE, TOKEN, "cis2_test_token.approve", &approval).expect("Approve");
+
+    let params = StakeParams { beneficiary: None, amount: TokenAmountU64(1_000), stake_id: None, tier: Some(2) };
+    let error = update(&mut chain, ALICE, contract, "stake_funds", &params).expect_err("Unknown tier");
+    assert_eq!(error.parse_return_value(), Ok(StakingError::InvalidLockTier));
+
+    let params = StakeParams { beneficiary: None, amount: TokenAmountU64(1_000), stake_id: None, tier: None };
+    update(&mut chain, ALICE, contract, "stake_funds", &params).expect("Stake");
+    let params = StakeParams { beneficiary: None, amount: TokenAmountU64(1_000), stake_id: Some(1), tier: Some(1) };
+    let error = update(&mut chain, ALICE, contract, "stake_funds", &params).expect_err("Tier of another position");
+    assert_eq!(error.parse_return_value(), Ok(StakingError::InvalidLockTier));
+    assert_eq!(stake_info(&chain, contract, 1).amount, TokenAmountU64(1_000));
+}
+
+/// Releasing takes the principal out of `total_staked` and the rewards out of
+/// the reward pool.
+#[test]
+fn test_release_updates_pool() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    stake(&mut chain, contract, BOB, 3_000).expect("Stake");
+    fund_rewards(&mut chain, contract, 500).expect("Fund rewards");
+
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "release_funds", &ReleaseFundsParams { stake_id: 1 }).expect("Release");
+
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 1_100);
+    assert_eq!(stake_info(&chain, contract, 1).state, StakeEntryState::Inactive);
+    let stats: PoolStats = view(&chain, contract, "view_pool_stats", &());
+    assert_eq!((stats.total_staked, stats.reward_pool_balance), (TokenAmountU64(3_000), TokenAmountU64(400)));
+    assert_eq!(balance_of(&chain, Address::Contract(contract)), 3_400);
+}
+
+/// An exhausted reward pool does not hold back the principal. Releasing pays
+/// the principal and the rewards the pool covers, and the position stays open
+/// until the rest of its rewards are claimed.
+#[test]
+fn test_release_with_exhausted_pool() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    stake(&mut chain, contract, BOB, 1_000).expect("Stake");
+    fund_rewards(&mut chain, contract, 30).expect("Fund rewards");
+
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "release_funds", &ReleaseFundsParams { stake_id: 1 }).expect("Release");
+
+    // The principal and the 30 in the pool, out of the 100 owed.
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 1_030);
+    let stake_entry = stake_info(&chain, contract, 1);
+    assert_eq!((stake_entry.amount, stake_entry.pending_rewards, stake_entry.state),
+        (TokenAmountU64(0), TokenAmountU64(70), StakeEntryState::Active));
+    let stats: PoolStats = view(&chain, contract, "view_pool_stats", &());
+    assert_eq!((stats.total_staked, stats.reward_pool_balance), (TokenAmountU64(1_000), TokenAmountU64(0)));
+    // The other staker's principal is untouched.
+    assert_eq!(balance_of(&chain, Address::Contract(contract)), 1_000);
+
+    // Once the pool is funded again, the rest is claimed and the position
+    // closes.
+    fund_rewards(&mut chain, contract, 500).expect("Fund rewards");
+    update(&mut chain, ALICE, contract, "claim_rewards", &1u64).expect("Claim rewards");
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 1_100);
+    assert_eq!(stake_info(&chain, contract, 1).state, StakeEntryState::Inactive);
+    assert_eq!(view::<PoolStats>(&chain, contract, "view_pool_stats", &()).active_stakers, 1);
+}
+
+/// An exhausted reward pool does not hold back unstaking either.
+#[test]
+fn test_unstake_with_exhausted_pool() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    update(&mut chain, ALICE, contract, "set_auto_compound", &SetAutoCompoundParams { stake_id: 1, auto_compound: true })
+        .expect("Set auto-compound");
+
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "unstake", &UnstakeParams { stake_id: 1, amount: TokenAmountU64(1_000) }).expect("Unstake");
+
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 1_000);
+    let stake_entry = stake_info(&chain, contract, 1);
+    assert_eq!((stake_entry.amount, stake_entry.pending_rewards), (TokenAmountU64(0), TokenAmountU64(100)));
+}
+
+/// Claiming or compounding rewards owed from an empty reward pool fails, and
+/// leaves the rewards owed.
+#[test]
+fn test_claim_with_exhausted_pool() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    for entrypoint in ["claim_rewards", "compound"] {
+        let error = update(&mut chain, ALICE, contract, entrypoint, &1u64).expect_err("Reward pool is empty");
+        assert_eq!(error.parse_return_value(), Ok(StakingError::RewardPoolExhausted));
+    }
+
+    fund_rewards(&mut chain, contract, 500).expect("Fund rewards");
+    update(&mut chain, ALICE, contract, "claim_rewards", &1u64).expect("Claim rewards");
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 100);
+}
+
+/// A top-up paid by someone else adds to the position of the beneficiary but
+/// does not extend its lock.
+#[test]
+fn test_third_party_top_up_keeps_release_time() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    let release_time = stake_info(&chain, contract, 1).release_time;
+
+    chain.tick_block_time(Duration::from_days(20)).expect("Block time in range");
+    let data = StakeTransferData { beneficiary: Some(Address::Account(ALICE)), stake_id: Some(1), tier: None };
+    stake_with(&mut chain, contract, BOB, 500, &data).expect("Top up");
+
+    let stake_entry = stake_info(&chain, contract, 1);
+    assert_eq!((stake_entry.amount, stake_entry.release_time), (TokenAmountU64(1_500), release_time));
+
+    // The staker's own top-up restarts the lock.
+    stake_with(&mut chain, contract, ALICE, 500, &StakeTransferData { stake_id: Some(1), ..Default::default() }).expect("Top up");
+    assert_eq!(stake_info(&chain, contract, 1).release_time, release_time.checked_add(Duration::from_days(20)).unwrap());
+}
+
+/// Staking no tokens is rejected, for new positions and top-ups alike.
+#[test]
+fn test_stake_zero_amount() {
+    let (mut chain, contract) = initialize();
+    let approval = ApproveParam { amount: TokenAmountU64(0), spender: Address::Contract(contract), token_id: TokenIdUnit() };
+    call(&mut chain, ALICE, TOKEN, "cis2_test_token.approve", &approval).expect("Approve");
+    let params = StakeParams { beneficiary: None, amount: TokenAmountU64(0), stake_id: None, tier: None };
+    let update = update(&mut chain, ALICE, contract, "stake_funds", &params).expect_err("Stake nothing");
+    assert_eq!(update.parse_return_value(), Ok(StakingError::InvalidAmount));
+    stake(&mut chain, contract, BOB, 0).expect_err("Stake nothing");
+
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    let data = StakeTransferData { beneficiary: Some(Address::Account(ALICE)), stake_id: Some(1), tier: None };
+    stake_with(&mut chain, contract, BOB, 0, &data).expect_err("Top up nothing");
+    assert_eq!(view::<PoolStats>(&chain, contract, "view_pool_stats", &()).active_stakers, 1);
+}
+
+/// A stake cannot be released before its release time, and can be from then
+/// on.
+#[test]
+fn test_release_after_lock() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    fund_rewards(&mut chain, contract, 1_000).expect("Fund rewards");
+
+    // One millisecond before the end of the 30 day lock.
+    chain.tick_block_time(Duration::from_millis(Duration::from_days(30).millis() - 1)).expect("Block time in range");
+    let error = update(&mut chain, ALICE, contract, "release_funds", &ReleaseFundsParams { stake_id: 1 })
+        .expect_err("Release while locked");
+    assert_eq!(error.parse_return_value(), Ok(StakingError::InvalidReleaseTime));
+
+    chain.tick_block_time(Duration::from_millis(1)).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "release_funds", &ReleaseFundsParams { stake_id: 1 }).expect("Release");
+    assert_eq!(stake_info(&chain, contract, 1).state, StakeEntryState::Inactive);
+    // The principal and 30 days at 10% on it, rounded down.
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 1_008);
+}
+
+/// Pausing staking halts new stakes but not releases, until it is unpaused.
+#[test]
+fn test_pause_staking() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    fund_rewards(&mut chain, contract, 500).expect("Fund rewards");
+    update(&mut chain, ALICE, contract, "set_paused", &PauseFlags { staking: true, releases: false }).expect("Pause staking");
+
+    let error = update(&mut chain, ALICE, contract, "compound", &1u64).expect_err("Compound while paused");
+    assert_eq!(error.parse_return_value(), Ok(StakingError::ContractPaused));
+    stake(&mut chain, contract, ALICE, 1_000).expect_err("Stake while paused");
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "claim_rewards", &1u64).expect("Claim rewards while staking is paused");
+
+    update(&mut chain, ALICE, contract, "set_paused", &PauseFlags::default()).expect("Unpause");
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+}
+
+/// Pausing releases halts releases, unstaking and claims but not staking,
+/// until it is unpaused.
+#[test]
+fn test_pause_releases() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    fund_rewards(&mut chain, contract, 1_000).expect("Fund rewards");
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "set_paused", &PauseFlags { staking: false, releases: true }).expect("Pause releases");
+
+    let errors = [
+        update(&mut chain, ALICE, contract, "release_funds", &ReleaseFundsParams { stake_id: 1 }),
+        update(&mut chain, ALICE, contract, "unstake", &UnstakeParams { stake_id: 1, amount: TokenAmountU64(1) }),
+        update(&mut chain, ALICE, contract, "claim_rewards", &1u64),
+    ];
+    for error in errors {
+        assert_eq!(error.expect_err("Paused").parse_return_value(), Ok(StakingError::ContractPaused));
+    }
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake while releases are paused");
+
+    update(&mut chain, ALICE, contract, "set_paused", &PauseFlags::default()).expect("Unpause");
+    update(&mut chain, ALICE, contract, "release_funds", &ReleaseFundsParams { stake_id: 1 }).expect("Release");
+}
+
+/// Claiming pays out the rewards so far and keeps the principal staked until
+/// its release time.
+#[test]
+fn test_claim_rewards_keeps_stake() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    fund_rewards(&mut chain, contract, 1_000).expect("Fund rewards");
+    let before = stake_info(&chain, contract, 1);
+
+    chain.tick_block_time(Duration::from_days(73)).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "claim_rewards", &1u64).expect("Claim rewards");
+
+    // 73 days at 10% on 1_000.
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 20);
+    let after = stake_info(&chain, contract, 1);
+    assert_eq!((after.amount, after.release_time, after.state), (before.amount, before.release_time, StakeEntryState::Active));
+    assert_eq!(view::<PoolStats>(&chain, contract, "view_pool_stats", &()).total_staked, TokenAmountU64(1_000));
+}
+
+/// Claiming when no rewards are owed pays out and logs nothing.
+#[test]
+fn test_claim_zero_rewards() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+
+    let update = update(&mut chain, ALICE, contract, "claim_rewards", &1u64).expect("Claim rewards");
+
+    assert!(update.events().all(|(_, events)| events.is_empty()));
+    assert!(!update.effective_trace_elements().any(|element| matches!(element, ContractTraceElement::Interrupted { .. })));
+}
+
+/// Compounding when no rewards are owed changes and logs nothing.
+#[test]
+fn test_compound_zero_rewards() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    let stake_entry = stake_info(&chain, contract, 1);
+
+    let update = update(&mut chain, ALICE, contract, "compound", &1u64).expect("Compound");
+
+    assert!(update.events().all(|(_, events)| events.is_empty()));
+    assert_eq!(stake_info(&chain, contract, 1), stake_entry);
+}
+
+/// Tokens from a contract other than the configured token are rejected.
+#[test]
+fn test_receive_from_other_token() {
+    let (mut chain, contract) = initialize();
+    let mod_ref = chain.get_contract(TOKEN).expect("Token exists").module_reference;
+    let other_token = init_contract(&mut chain, mod_ref, "init_cis2_test_token", OwnedParameter::empty());
+
+    let error = receive_tokens(&mut chain, contract, other_token, TokenIdVec(Vec::new())).expect_err("Other token");
+    assert_eq!(error.parse_return_value(), Ok(StakingError::InvalidToken));
+}
+
+/// Tokens of the configured contract with another token ID are rejected.
+#[test]
+fn test_receive_other_token_id() {
+    let (mut chain, contract) = initialize();
+
+    let error = receive_tokens(&mut chain, contract, TOKEN, TokenIdVec(vec![1])).expect_err("Other token ID");
+    assert_eq!(error.parse_return_value(), Ok(StakingError::InvalidToken));
+}
+
+/// A transfer without data opens a position with the default lock terms for
+/// the sender of the tokens.
+#[test]
+fn test_receive_without_data() {
+    let (mut chain, contract) = initialize();
+    mint(&mut chain, Address::Account(BOB), 1_000);
+    transfer(&mut chain, BOB, contract, "onReceivingCIS2", 1_000, AdditionalData::empty()).expect("Stake");
+
+    let stake_entry = stake_info(&chain, contract, 1);
+    assert_eq!((stake_entry.staker, stake_entry.amount, stake_entry.tier), (Address::Account(BOB), TokenAmountU64(1_000), None));
+    assert_eq!(stake_entry.release_time, stake_entry.accrual_start.checked_add(Duration::from_days(30)).unwrap());
+    assert_eq!(balance_of(&chain, Address::Contract(contract)), 1_000);
+}
+
+/// Staking and releasing log events describing the changes.
+#[test]
+fn test_stake_and_release_events() {
+    let (mut chain, contract) = initialize();
+    let staked = stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    let release_time = stake_info(&chain, contract, 1).release_time;
+    assert_eq!(staking_events(&staked, contract), vec![StakingEvent::Staked {
+        stake_id: 1,
+        staker: Address::Account(ALICE),
+        payer: Address::Account(ALICE),
+        amount: TokenAmountU64(1_000),
+        tier: None,
+        release_time,
+    }]);
+    fund_rewards(&mut chain, contract, 500).expect("Fund rewards");
+
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    let released = update(&mut chain, ALICE, contract, "release_funds", &ReleaseFundsParams { stake_id: 1 }).expect("Release");
+
+    assert_eq!(staking_events(&released, contract), vec![
+        StakingEvent::RewardPaid { stake_id: 1, staker: Address::Account(ALICE), amount: TokenAmountU64(100) },
+        StakingEvent::Released { stake_id: 1, staker: Address::Account(ALICE), amount: TokenAmountU64(1_000) },
+    ]);
+}
+
+/// A contract can stake and release, receiving its payouts on
+/// `onReceivingCIS2` or on the entrypoint it chose.
+#[test]
+fn test_contract_staker() {
+    let (mut chain, contract) = initialize();
+    let wallet = initialize_wallet(&mut chain);
+    fund_rewards(&mut chain, contract, 1_000).expect("Fund rewards");
+    wallet_stake(&mut chain, wallet, contract, 1_000);
+    assert_eq!(stake_info(&chain, contract, 1).staker, Address::Contract(wallet));
+
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    let released = forward(&mut chain, wallet, contract, "release_funds", &ReleaseFundsParams { stake_id: 1 }).expect("Release");
+    assert_eq!(received_on(&released, wallet), vec!["onReceivingCIS2".to_string()]);
+    assert_eq!(balance_of(&chain, Address::Contract(wallet)), 1_100);
+
+    forward(&mut chain, wallet, contract, "set_payout_entrypoint", &OwnedEntrypointName::new_unchecked("receive_payout".to_string()))
+        .expect("Set payout entrypoint");
+    wallet_stake(&mut chain, wallet, contract, 1_000);
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    let released = forward(&mut chain, wallet, contract, "release_funds", &ReleaseFundsParams { stake_id: 2 }).expect("Release");
+    assert_eq!(received_on(&released, wallet), vec!["receive_payout".to_string()]);
+    assert_eq!(balance_of(&chain, Address::Contract(wallet)), 2_200);
+}
+
+/// `stake_funds` pulls the approved tokens from the sender and stakes them.
+#[test]
+fn test_stake_funds() {
+    let (mut chain, contract) = initialize();
+    mint(&mut chain, Address::Account(ALICE), 1_000);
+    let approval = ApproveParam { amount: TokenAmountU64(1_000), spender: Address::Contract(contract), token_id: TokenIdUnit() };
+    call(&mut chain, ALICE, TOKEN, "cis2_test_token.approve", &approval).expect("Approve");
+
+    let params = StakeParams { beneficiary: None, amount: TokenAmountU64(1_000), stake_id: None, tier: None };
+    let update = update(&mut chain, ALICE, contract, "stake_funds", &params).expect("Stake");
+
+    assert_eq!(update.parse_return_value::<StakeId>(), Ok(1));
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 0);
+    assert_eq!(balance_of(&chain, Address::Contract(contract)), 1_000);
+    assert_eq!(stake_info(&chain, contract, 1).staker, Address::Account(ALICE));
+}
+
+/// Unstaking part of a stake pays out that part and the rewards so far, and
+/// leaves the rest staked and accruing.
+#[test]
+fn test_unstake_partial() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    fund_rewards(&mut chain, contract, 1_000).expect("Fund rewards");
+
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "unstake", &UnstakeParams { stake_id: 1, amount: TokenAmountU64(400) }).expect("Unstake");
+
+    // The 400 unstaked and a year at 10% on 1000.
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 500);
+    let stake_entry = stake_info(&chain, contract, 1);
+    assert_eq!((stake_entry.amount, stake_entry.state), (TokenAmountU64(600), StakeEntryState::Active));
+
+    // A year at 10% on the 600 left.
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "claim_rewards", &1u64).expect("Claim rewards");
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 560);
+}
+
+/// Unstaking the whole stake closes the position.
+#[test]
+fn test_unstake_all() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+    fund_rewards(&mut chain, contract, 1_000).expect("Fund rewards");
+
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    update(&mut chain, ALICE, contract, "unstake", &UnstakeParams { stake_id: 1, amount: TokenAmountU64(1_000) }).expect("Unstake");
+
+    assert_eq!(balance_of(&chain, Address::Account(ALICE)), 1_100);
+    assert_eq!(stake_info(&chain, contract, 1).state, StakeEntryState::Inactive);
+    assert_eq!(view::<Vec<StakeId>>(&chain, contract, "get_closed_stakes", &Address::Account(ALICE)), vec![1]);
+    assert_eq!(view::<PoolStats>(&chain, contract, "view_pool_stats", &()).active_stakers, 0);
+}
+
+/// More than the stake cannot be unstaked.
+#[test]
+fn test_unstake_more_than_staked() {
+    let (mut chain, contract) = initialize();
+    stake(&mut chain, contract, ALICE, 1_000).expect("Stake");
+
+    chain.tick_block_time(ONE_YEAR).expect("Block time in range");
+    let update = update(&mut chain, ALICE, contract, "unstake", &UnstakeParams { stake_id: 1, amount: TokenAmountU64(1_001) })
+        .expect_err("Unstake more than staked");
+
+    assert_eq!(update.parse_return_value(), Ok(StakingError::InsufficientFunds));
+    assert_eq!(balance_of(&chain, Address::Contract(contract)), 1_000);
+}
+
+/// Helper method for initializing the contract from the current module.
+fn initialize() -> (Chain, ContractAddress) { initialize_module(MODULE_PATH) }
+
+/// Helper method for initializing the contract.
+///
+/// Does the following:
+///  - Creates the [`Chain`]
+///  - Creates the accounts `ALICE` and `BOB`.
+///  - Deploys the test contracts and initializes the test token at `TOKEN`.
+///  - Deploys the module at `module_path` and initializes the contract with
+///    `ALICE` as admin and the test token as its token.
+///  - Returns the [`Chain`] and the address of the contract.
+fn initialize_module(module_path: &str) -> (Chain, ContractAddress) {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(ALICE, ACC_INITIAL_BALANCE));
+    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
+
+    let test_contracts = module_load_v1(TEST_CONTRACTS_PATH).expect("Module exists at path");
+    let test_contracts = chain.module_deploy_v1(SIGNER, ALICE, test_contracts).expect("Deploy valid module").module_reference;
+    let token = init_contract(&mut chain, test_contracts, "init_cis2_test_token", OwnedParameter::empty());
+    assert_eq!(token, TOKEN);
+
+    let module = module_load_v1(module_path).expect("Module exists at path");
+    let deployment = chain.module_deploy_v1(SIGNER, ALICE, module).expect("Deploy valid module");
+
+    let params = InitParams {
+        token: TOKEN,
+        token_id: TokenIdVec(Vec::new()),
+        reward_rate_bps: TEN_PERCENT_BPS,
+        min_lock_duration: Duration::from_days(30),
+    };
+    let param = OwnedParameter::from_serial(&params).expect("Parameter within size bounds");
+    let contract = init_contract(&mut chain, deployment.module_reference, "init_gonana_staking_smart_contract", param);
+
+    (chain, contract)
+}
+
+/// Initializes the contract `init_name` of module `mod_ref`.
+fn init_contract(chain: &mut Chain, mod_ref: ModuleReference, init_name: &str, param: OwnedParameter) -> ContractAddress {
+    chain
+        .contract_init(SIGNER, ALICE, Energy::from(10_000), InitContractPayload {
+            amount: Amount::zero(),
+            mod_ref,
+            init_name: OwnedContractName::new_unchecked(init_name.to_string()),
+            param,
+        })
+        .expect("Initializing contract")
+        .contract_address
+}
+
+/// Initializes a `test_wallet`, a contract that can stake.
+fn initialize_wallet(chain: &mut Chain) -> ContractAddress {
+    let mod_ref = chain.get_contract(TOKEN).expect("Token exists").module_reference;
+    init_contract(chain, mod_ref, "init_test_wallet", OwnedParameter::empty())
+}
+
+/// Has `wallet` call `entrypoint` of `contract`.
+fn forward<P: Serial>(chain: &mut Chain, wallet: ContractAddress, contract: ContractAddress, entrypoint: &str, params: &P) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    let params = (
+        contract,
+        OwnedEntrypointName::new_unchecked(entrypoint.to_string()),
+        OwnedParameter::from_serial(params).expect("Parameter within size bounds"),
+    );
+    call(chain, ALICE, wallet, "test_wallet.forward", &params)
+}
+
+/// Mints `amount` test tokens for `wallet`, which stakes them through a token
+/// transfer to `onReceivingCIS2`.
+fn wallet_stake(chain: &mut Chain, wallet: ContractAddress, contract: ContractAddress, amount: u64) {
+    mint(chain, Address::Contract(wallet), amount);
+    let transfer = Transfer {
+        token_id: TokenIdVec(Vec::new()),
+        amount: TokenAmountU64(amount),
+        from: Address::Contract(wallet),
+        to: Receiver::Contract(contract, OwnedEntrypointName::new_unchecked("onReceivingCIS2".to_string())),
+        data: AdditionalData::empty(),
+    };
+    forward(chain, wallet, TOKEN, "transfer", &TransferParams(vec![transfer])).expect("Stake");
+}
+
+/// The entrypoints of `wallet` called by the token contract during `update`.
+fn received_on(update: &ContractInvokeSuccess, wallet: ContractAddress) -> Vec<String> {
+    update
+        .effective_trace_elements()
+        .filter_map(|element| match element {
+            ContractTraceElement::Updated { data } if data.address == wallet && data.instigator == Address::Contract(TOKEN) => {
+                Some(data.receive_name.as_receive_name().entrypoint_name().to_string())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Deploys a second version of the module. It has the same code with an extra
+/// custom section, so it gets a module reference of its own.
+fn deploy_new_version(chain: &mut Chain) -> ModuleReference {
+    let module = std::fs::read(MODULE_PATH).expect("Module exists at path");
+    // Skip the version and length prefix of the module.
+    let mut wasm = module[8..].to_vec();
+    let name = b"version";
+    wasm.extend([0, name.len() as u8 + 2, name.len() as u8]);
+    wasm.extend(name);
+    wasm.push(2);
+
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("module_v2.wasm");
+    std::fs::write(&path, wasm).expect("Write module");
+    let module = module_load_v1_raw(path).expect("Module exists at path");
+    chain.module_deploy_v1(SIGNER, ALICE, module).expect("Deploy valid module").module_reference
+}
+
+/// Mints `amount` test tokens for `staker`, who stakes them through a token
+/// transfer to `onReceivingCIS2`.
+fn stake(chain: &mut Chain, contract: ContractAddress, staker: AccountAddress, amount: u64) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    stake_with(chain, contract, staker, amount, &StakeTransferData::default())
+}
+
+/// Mints `amount` test tokens for `sender`, who transfers them to
+/// `onReceivingCIS2` with `data`.
+fn stake_with(chain: &mut Chain, contract: ContractAddress, sender: AccountAddress, amount: u64, data: &StakeTransferData) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    mint(chain, Address::Account(sender), amount);
+    transfer(chain, sender, contract, "onReceivingCIS2", amount, AdditionalData::from(to_bytes(data)))
+}
+
+/// Mints `amount` test tokens for `ALICE`, who adds them to the reward pool
+/// through a token transfer to `fund_rewards`.
+fn fund_rewards(chain: &mut Chain, contract: ContractAddress, amount: u64) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    mint(chain, Address::Account(ALICE), amount);
+    transfer(chain, ALICE, contract, "fund_rewards", amount, AdditionalData::empty())
+}
+
+/// Mints `amount` test tokens for `owner`.
+fn mint(chain: &mut Chain, owner: Address, amount: u64) {
+    call(chain, ALICE, TOKEN, "cis2_test_token.mint", &(owner, TokenAmountU64(amount))).expect("Mint");
+}
+
+/// Transfers `amount` test tokens of `from` to `entrypoint` of `contract`.
+fn transfer(chain: &mut Chain, from: AccountAddress, contract: ContractAddress, entrypoint: &str, amount: u64, data: AdditionalData) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    let transfer = Transfer {
+        token_id: TokenIdVec(Vec::new()),
+        amount: TokenAmountU64(amount),
+        from: Address::Account(from),
+        to: Receiver::Contract(contract, OwnedEntrypointName::new_unchecked(entrypoint.to_string())),
+        data,
+    };
+    call(chain, from, TOKEN, "cis2_test_token.transfer", &TransferParams(vec![transfer]))
+}
+
+/// Calls `onReceivingCIS2` on `contract` as the token contract `token` would
+/// when `ALICE` transfers it 1_000 of `token_id`.
+fn receive_tokens(chain: &mut Chain, contract: ContractAddress, token: ContractAddress, token_id: TokenIdVec) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    let params = OnReceivingCis2Params {
+        token_id,
+        amount: TokenAmountU64(1_000),
+        from: Address::Account(ALICE),
+        data: AdditionalData::empty(),
+    };
+    chain.contract_update(SIGNER, ALICE, Address::Contract(token), Energy::from(100_000), UpdateContractPayload {
+        address:      contract,
+        amount:       Amount::zero(),
+        receive_name: OwnedReceiveName::new_unchecked("gonana_staking_smart_contract.onReceivingCIS2".to_string()),
+        message:      OwnedParameter::from_serial(&params).expect("Parameter within size bounds"),
+    })
+}
+
+/// The test token balance of `owner`.
+fn balance_of(chain: &Chain, owner: Address) -> u64 {
+    let query = BalanceOfQueryParams { queries: vec![BalanceOfQuery { token_id: TokenIdVec(Vec::new()), address: owner }] };
+    let balances: BalanceOfQueryResponse<TokenAmountU64> = chain
+        .contract_invoke(ALICE, Address::Account(ALICE), Energy::from(100_000), UpdateContractPayload {
+            address:      TOKEN,
+            amount:       Amount::zero(),
+            receive_name: OwnedReceiveName::new_unchecked("cis2_test_token.balanceOf".to_string()),
+            message:      OwnedParameter::from_serial(&query).expect("Parameter within size bounds"),
+        })
+        .expect("Query balance")
+        .parse_return_value()
+        .expect("Deserialize balances");
+    balances.0[0].0
+}
+
+/// The migration of the current module, to be called by `upgrade`.
+fn migration() -> (OwnedEntrypointName, OwnedParameter) {
+    (OwnedEntrypointName::new_unchecked("migrate".to_string()), OwnedParameter::empty())
+}
+
+/// Calls `upgrade` on `contract` as `sender`.
+fn upgrade(chain: &mut Chain, sender: AccountAddress, contract: ContractAddress, params: &UpgradeParams) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    update(chain, sender, contract, "upgrade", params)
+}
+
+/// Calls `entrypoint` on `contract` as `sender`.
+fn update<P: Serial>(chain: &mut Chain, sender: AccountAddress, contract: ContractAddress, entrypoint: &str, params: &P) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    call(chain, sender, contract, &format!("gonana_staking_smart_contract.{entrypoint}"), params)
+}
+
+/// Calls the receive function `receive_name` on `contract` as `sender`.
+fn call<P: Serial>(chain: &mut Chain, sender: AccountAddress, contract: ContractAddress, receive_name: &str, params: &P) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    chain.contract_update(SIGNER, sender, Address::Account(sender), Energy::from(100_000), UpdateContractPayload {
+        address:      contract,
+        amount:       Amount::zero(),
+        receive_name: OwnedReceiveName::new_unchecked(receive_name.to_string()),
+        message:      OwnedParameter::from_serial(params).expect("Parameter within size bounds"),
+    })
+}
+
+/// The position `stake_id` of `contract`.
+fn stake_info(chain: &Chain, contract: ContractAddress, stake_id: StakeId) -> StakeEntry {
+    view::<Option<StakeEntry>>(chain, contract, "get_stake_info", &stake_id).expect("Position exists")
+}
+
+/// The events logged by `contract` during `update`.
+fn staking_events(update: &ContractInvokeSuccess, contract: ContractAddress) -> Vec<StakingEvent> {
+    update
+        .events()
+        .filter(|(address, _)| *address == contract)
+        .flat_map(|(_, events)| events.iter().map(|event| event.parse().expect("Deserialize event")))
+        .collect()
+}
+
+/// Invokes the view `entrypoint` on `contract` and parses its return value.
+fn view<R: Deserial>(chain: &Chain, contract: ContractAddress, entrypoint: &str, params: &impl Serial) -> R {
+    invoke(chain, contract, entrypoint, params)
+        .expect("View succeeds")
+        .parse_return_value()
+        .expect("Deserialize return value")
+}
+
+/// Invokes the view `entrypoint` on `contract`.
+fn invoke(chain: &Chain, contract: ContractAddress, entrypoint: &str, params: &impl Serial) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    chain.contract_invoke(ALICE, Address::Account(ALICE), Energy::from(100_000), UpdateContractPayload {
+        address:      contract,
+        amount:       Amount::zero(),
+        receive_name: OwnedReceiveName::new_unchecked(format!("gonana_staking_smart_contract.{entrypoint}")),
+        message:      OwnedParameter::from_serial(params).expect("Parameter within size bounds"),
+    })
+}